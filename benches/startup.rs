@@ -0,0 +1,126 @@
+//! How long covidd takes from its start until the cached data is ready.
+//!
+//! The encoding of the cache is chosen when covidd is built, compare
+//! `cargo bench` with `cargo bench --features binary-cache`.
+
+use chrono::{Duration, TimeZone, Utc};
+use criterion::{criterion_group, criterion_main, Criterion};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+const YEARS: i64 = 5;
+
+fn data_points(years: i64) -> Vec<Value> {
+    let start = Utc.ymd(2020, 3, 1);
+    (0..years * 365)
+        .map(|day| {
+            let date = start + Duration::days(day);
+            let n = day as u32;
+            json!({
+                "ObjectId": n,
+                "Datum": date.format("%d.%m.%Y").to_string(),
+                "Datum_neu": date.and_hms(12, 0, 0).timestamp_millis(),
+                "Zeitraum": date.format("%d.%m.%Y").to_string(),
+                "Anzeige_Indikator": if day % 7 == 0 { Some("x") } else { None },
+                "Inzidenz": f64::from(n % 500) * 1.5,
+                "Inzidenz_Berechnet": f64::from(n % 500) * 1.4,
+                "Fallzahl": n * 100,
+                "Zuwachs_Fallzahl": 100,
+                "Fälle_Meldedatum": 90 + n % 20,
+                "Sterbefall": n,
+                "Zuwachs_Sterbefall": 1,
+                "Genesungsfall": n * 90,
+                "Zuwachs_Genesung": 90,
+                "Hospitalisierung": n * 3,
+                "Zuwachs_Krankenhauseinweisung": -2,
+                "BelegteBetten": n % 300,
+            })
+        })
+        .collect()
+}
+
+fn covidd(cache_dir: &Path) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_covidd"));
+    command.arg("--cache-dir").arg(cache_dir);
+    command
+}
+
+fn run(command: &mut Command) {
+    let output = command.output().unwrap();
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+/// A JSON cache, that every build of covidd can read
+fn json_cache(dir: &Path, cache: &str) -> PathBuf {
+    let cache_dir = dir.join("json");
+    fs::create_dir_all(&cache_dir).unwrap();
+    fs::write(cache_dir.join("cached_data.json"), cache).unwrap();
+    cache_dir
+}
+
+/// A cache in the encoding of this build, written by importing a bundle
+fn built_cache(dir: &Path, cache: &str) -> PathBuf {
+    let hash = Sha256::digest(cache.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    let bundle = format!(
+        r#"{{"format":"covidd-bundle","version":1,"checksum":"sha256:{}","cache":{}}}"#,
+        hash, cache
+    );
+    let bundle_file = dir.join("bundle.json");
+    fs::write(&bundle_file, bundle).unwrap();
+
+    let cache_dir = dir.join("built");
+    run(covidd(&cache_dir)
+        .args(["cache", "import"])
+        .arg(&bundle_file));
+    cache_dir
+}
+
+fn startup(c: &mut Criterion) {
+    let dir = std::env::temp_dir().join(format!("covidd-bench-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    let cache = json!({
+        "version": 3,
+        // fresh for as long as the benchmark runs
+        "created_at": Utc::now().timestamp(),
+        "population": 556_780,
+        "attributes": data_points(YEARS),
+    })
+    .to_string();
+    let caches = [
+        ("json cache", json_cache(&dir, &cache)),
+        ("built cache", built_cache(&dir, &cache)),
+    ];
+    let output = dir.join("export.csv");
+
+    let mut group = c.benchmark_group(format!("startup with {} years of data", YEARS));
+    group.sample_size(20);
+    for (name, cache_dir) in &caches {
+        group.bench_function(*name, |b| {
+            b.iter(|| {
+                run(covidd(cache_dir)
+                    .args(["export", "--cache", "-o"])
+                    .arg(&output))
+            })
+        });
+    }
+    group.finish();
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+criterion_group!(benches, startup);
+criterion_main!(benches);
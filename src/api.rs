@@ -8,21 +8,29 @@ use std::{convert::TryFrom, io::Cursor, time::Duration};
 use tracing::{debug, instrument, warn};
 
 static UA: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+static POP_URL: &str = "https://opendata.dresden.de/duva2ckan/files/de-sn-dresden-einwohner___md_34e_2020_-_3006_od_bevoelkerung_ab_stadtteil_hauptwohner_geschlecht_deutsche__auslaender/content";
+static CSV_URL: &str = "https://opendata.dresden.de/duva2ckan/files/de-sn-dresden-corona_-_covid-19_-_fallzahlen_md1_dresden_2020ff/content";
+static CURRENT_URL: &str = "https://services.arcgis.com/ORpvigFPJUhb8RDF/arcgis/rest/services/corona_DD_7_Sicht/FeatureServer/0/query";
 
+/// The endpoints that the population, the full history and the latest data are fetched from
+pub fn sources() -> [&'static str; 3] {
+    [POP_URL, CSV_URL, CURRENT_URL]
+}
+
+/// Fetches and corrects the data, together with the population it was corrected with
 #[instrument(err)]
-pub fn call(timeout: Duration) -> Result<Data> {
-    let population = f64::from(populace(timeout)?);
+pub fn call(timeout: Duration) -> Result<(Data, u32)> {
+    let populace = populace(timeout)?;
+    let population = f64::from(populace);
 
     let initial_data = get_full_data(timeout, population)?;
     let current_data = get_current_data(timeout, population, initial_data.len())?;
     let data = correct_data(vec![initial_data, current_data], population);
-    Ok(data)
+    Ok((data, populace))
 }
 
 #[instrument(err)]
 pub fn populace(timeout: Duration) -> Result<u32> {
-    static POP_URL: &str = "https://opendata.dresden.de/duva2ckan/files/de-sn-dresden-einwohner___md_34e_2020_-_3006_od_bevoelkerung_ab_stadtteil_hauptwohner_geschlecht_deutsche__auslaender/content";
-
     debug!("Reading population info from API");
 
     let data = minreq::get(POP_URL)
@@ -52,7 +60,10 @@ pub fn populace(timeout: Duration) -> Result<u32> {
 
 #[instrument(err)]
 pub fn get_current_data(timeout: Duration, population: f64, skip: usize) -> Result<Data> {
-    let url = format!("https://services.arcgis.com/ORpvigFPJUhb8RDF/arcgis/rest/services/corona_DD_7_Sicht/FeatureServer/0/query?f=pjson&where=ObjectId%3E{}&outFields=*", skip);
+    let url = format!(
+        "{}?f=pjson&where=ObjectId%3E{}&outFields=*",
+        CURRENT_URL, skip
+    );
 
     debug!("Reading from API: {}", url);
 
@@ -73,8 +84,6 @@ pub fn get_current_data(timeout: Duration, population: f64, skip: usize) -> Resu
 
 #[instrument(err)]
 pub fn get_full_data(timeout: Duration, population: f64) -> Result<Vec<DataPoint>> {
-    static CSV_URL: &str = "https://opendata.dresden.de/duva2ckan/files/de-sn-dresden-corona_-_covid-19_-_fallzahlen_md1_dresden_2020ff/content";
-
     debug!("Reading CSV from data portal");
 
     let data = minreq::get(CSV_URL)
@@ -1,19 +1,32 @@
-use clap::{ArgAction, Parser};
+use crate::{
+    cache, correlation, export, output::Format, projection, rates, resample::Resolution, waves,
+    Result,
+};
+use chrono::NaiveDate;
+use clap::{ArgAction, ArgGroup, Args as ClapArgs, Parser};
 use humantime::Duration;
+use std::path::PathBuf;
 
 impl Command {
-    pub fn get() -> Self {
+    pub fn get() -> (Self, CacheLocation) {
         let args = Args::parse();
-        match args.cmd {
+        let cmd = match args.cmd {
             Some(cmd) => cmd,
             None => Command::Run(args.run),
-        }
+        };
+        (cmd, args.location)
     }
 
     pub fn verbosity(&self) -> i8 {
         match self {
             Command::Cache(_) => 2,
-            Command::Run(r) => (r.verbose as i8) - (r.quiet as i8),
+            Command::Forecast(f) => f.verbosity.level(),
+            Command::Waves(w) => w.verbosity.level(),
+            Command::Lags(l) => l.verbosity.level(),
+            Command::Export(e) => e.verbosity.level(),
+            Command::Sql(s) => s.verbosity.level(),
+            Command::Diff(d) => d.verbosity.level(),
+            Command::Run(r) => r.verbosity.level(),
         }
     }
 }
@@ -31,6 +44,9 @@ struct Args {
     #[clap(flatten)]
     run: Run,
 
+    #[clap(flatten)]
+    location: CacheLocation,
+
     #[clap(subcommand)]
     cmd: Option<Command>,
 }
@@ -38,6 +54,118 @@ struct Args {
 /// Default run command
 #[derive(Parser, Debug)]
 pub struct Run {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    #[clap(flatten)]
+    pub source: Source,
+
+    /// Aggregate the data into points of this resolution
+    #[arg(short, long, value_enum, default_value_t = Resolution::Daily)]
+    pub resolution: Resolution,
+
+    /// Draw a projection of cases and incidence for this many data points
+    #[arg(long, value_name = "POINTS")]
+    pub forecast: Option<usize>,
+
+    /// Number of trailing data points that the projection is fitted on
+    #[arg(long, value_name = "POINTS", default_value_t = 14)]
+    pub forecast_window: usize,
+
+    /// Correct the most recent days for their expected revisions, estimated from the snapshots
+    #[arg(long)]
+    pub nowcast: bool,
+
+    #[clap(flatten)]
+    pub rates: Rates,
+
+    /// Minimal prominence of an incidence peak to be marked as a wave
+    #[arg(long, value_name = "INCIDENCE", default_value_t = waves::DEFAULT_PROMINENCE)]
+    pub wave_prominence: f64,
+
+    /// Compare the summary to the data from this long ago instead of the previous data point
+    #[arg(long, value_name = "DURATION")]
+    pub compare: Option<Duration>,
+
+    /// Show the data as it was known on this date, from the newest snapshot fetched until then
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub as_of: Option<NaiveDate>,
+
+    /// Print the output in this format instead of rendering the UI
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Print every data point instead of a summary of the latest one
+    #[arg(long)]
+    pub series: bool,
+
+    /// Skip the rendering of the UI
+    #[arg(long, hide = true)]
+    pub no_ui: bool,
+}
+
+impl Default for Run {
+    fn default() -> Self {
+        Self {
+            verbosity: Verbosity::default(),
+            source: Source::default(),
+            resolution: Resolution::Daily,
+            forecast: None,
+            forecast_window: 14,
+            nowcast: false,
+            rates: Rates::default(),
+            wave_prominence: waves::DEFAULT_PROMINENCE,
+            compare: None,
+            as_of: None,
+            format: Format::Text,
+            series: false,
+            no_ui: false,
+        }
+    }
+}
+
+impl Run {
+    pub fn projection(&self) -> Option<projection::Config> {
+        self.forecast.map(|horizon| projection::Config {
+            window: self.forecast_window,
+            horizon,
+        })
+    }
+}
+
+/// How the derived rates are calculated
+#[derive(ClapArgs, Debug, Copy, Clone)]
+pub struct Rates {
+    /// Number of data points over which the case fatality and hospitalisation rates are calculated
+    #[arg(long, value_name = "POINTS", default_value_t = 28)]
+    pub rate_window: usize,
+
+    /// Number of data points that deaths trail cases for the lagged case fatality rate
+    #[arg(long, value_name = "POINTS", default_value_t = 14)]
+    pub rate_lag: usize,
+}
+
+impl Default for Rates {
+    fn default() -> Self {
+        let config = rates::Config::default();
+        Self {
+            rate_window: config.window,
+            rate_lag: config.lag,
+        }
+    }
+}
+
+impl Rates {
+    pub fn config(&self) -> rates::Config {
+        rates::Config {
+            window: self.rate_window,
+            lag: self.rate_lag,
+        }
+    }
+}
+
+#[derive(ClapArgs, Debug, Default)]
+pub struct Verbosity {
     /// Print more logs, can be used multiple times
     #[arg(short, long, action = ArgAction::Count, conflicts_with = "quiet")]
     pub verbose: u8,
@@ -45,7 +173,48 @@ pub struct Run {
     /// Print less logs, can be used multiple times
     #[arg(short, long, action = ArgAction::Count, conflicts_with = "verbose")]
     pub quiet: u8,
+}
 
+impl Verbosity {
+    fn level(&self) -> i8 {
+        (self.verbose as i8) - (self.quiet as i8)
+    }
+}
+
+/// Where the cache is kept, for all commands
+#[derive(ClapArgs, Debug, Default)]
+pub struct CacheLocation {
+    /// Keep the cache in this directory instead of the user's cache directory
+    #[arg(long, value_name = "DIR", env = "COVIDD_CACHE_DIR", global = true)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Keep the cache in a directory next to the covidd binary
+    #[arg(long, global = true)]
+    pub portable: bool,
+
+    /// Neither read nor write any cache, always download new data
+    #[arg(long, global = true, conflicts_with = "portable")]
+    pub no_cache: bool,
+}
+
+impl CacheLocation {
+    /// The flags win over the directory, as it might come from the environment
+    pub fn location(&self) -> Result<cache::Location> {
+        Ok(if self.no_cache {
+            cache::Location::Disabled
+        } else if self.portable {
+            cache::Location::Dir(cache::portable_dir()?)
+        } else if let Some(dir) = &self.cache_dir {
+            cache::Location::Dir(dir.clone())
+        } else {
+            cache::Location::Default
+        })
+    }
+}
+
+/// Where the data is coming from
+#[derive(ClapArgs, Debug)]
+pub struct Source {
     /// Force download of new data before running
     #[arg(short, long, visible_alias = "download", conflicts_with = "cache")]
     pub force: bool,
@@ -62,21 +231,24 @@ pub struct Run {
     #[arg(short, long, conflicts_with = "cache", default_value = "10 seconds")]
     pub timeout: Duration,
 
-    /// Skip the rendering of the UI
-    #[arg(long, hide = true)]
-    pub no_ui: bool,
+    /// How long to wait for another covidd process that is already fetching new data
+    #[arg(
+        long,
+        value_name = "DURATION",
+        conflicts_with = "cache",
+        default_value = "30 seconds"
+    )]
+    pub lock_timeout: Duration,
 }
 
-impl Default for Run {
+impl Default for Source {
     fn default() -> Self {
         Self {
-            verbose: 0,
-            quiet: 0,
             force: false,
             cache: false,
             stale_after: Duration::from(std::time::Duration::from_secs(3600)),
             timeout: Duration::from(std::time::Duration::from_secs(10)),
-            no_ui: false,
+            lock_timeout: Duration::from(std::time::Duration::from_secs(30)),
         }
     }
 }
@@ -84,9 +256,151 @@ impl Default for Run {
 #[derive(Parser, Debug)]
 pub enum Command {
     Cache(Cache),
+    Forecast(Forecast),
+    Waves(Waves),
+    Lags(Lags),
+    Export(Export),
+    Sql(Sql),
+    Diff(Diff),
     Run(Run),
 }
 
+/// Print a short-term projection of cases and incidence
+#[derive(Parser, Debug)]
+pub struct Forecast {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    #[clap(flatten)]
+    pub source: Source,
+
+    /// Number of data points to project
+    #[arg(short = 'n', long, value_name = "POINTS", default_value_t = 14)]
+    pub horizon: usize,
+
+    /// Number of trailing data points that the projection is fitted on
+    #[arg(short, long, value_name = "POINTS", default_value_t = 14)]
+    pub window: usize,
+
+    /// Aggregate the data into points of this resolution before projecting
+    #[arg(short, long, value_enum, default_value_t = Resolution::Daily)]
+    pub resolution: Resolution,
+}
+
+impl Forecast {
+    pub fn projection(&self) -> projection::Config {
+        projection::Config {
+            window: self.window,
+            horizon: self.horizon,
+        }
+    }
+}
+
+/// List the waves of the incidence with their peaks
+#[derive(Parser, Debug)]
+pub struct Waves {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    #[clap(flatten)]
+    pub source: Source,
+
+    /// Minimal prominence of an incidence peak to be considered a wave
+    #[arg(short, long, value_name = "INCIDENCE", default_value_t = waves::DEFAULT_PROMINENCE)]
+    pub prominence: f64,
+}
+
+/// Find how many days hospitalisations and deaths trail the reported cases
+#[derive(Parser, Debug)]
+pub struct Lags {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    #[clap(flatten)]
+    pub source: Source,
+
+    /// Largest lag in data points that is tried
+    #[arg(short, long, value_name = "POINTS", default_value_t = correlation::DEFAULT_MAX_LAG)]
+    pub max_lag: usize,
+
+    /// Print the coefficients for every lag, not only the best one
+    #[arg(short, long, conflicts_with = "plot")]
+    pub all: bool,
+
+    /// Plot the series shifted by their best lag
+    #[arg(short, long)]
+    pub plot: bool,
+}
+
+/// Export the time series with English field names
+#[derive(Parser, Debug)]
+pub struct Export {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    #[clap(flatten)]
+    pub source: Source,
+
+    /// Only export data points on or after this date
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub since: Option<NaiveDate>,
+
+    /// Only export data points on or before this date
+    #[arg(long, value_name = "YYYY-MM-DD")]
+    pub until: Option<NaiveDate>,
+
+    /// Only export these metrics, can be repeated or comma separated
+    #[arg(short, long = "metric", value_name = "METRIC", value_delimiter = ',')]
+    pub metrics: Vec<String>,
+
+    /// Format of the export
+    #[arg(long, value_enum, default_value_t = export::Format::Csv)]
+    pub format: export::Format,
+
+    /// Write the export to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+
+    /// Aggregate the data into points of this resolution before exporting
+    #[arg(short, long, value_enum, default_value_t = Resolution::Daily)]
+    pub resolution: Resolution,
+
+    #[clap(flatten)]
+    pub rates: Rates,
+}
+
+/// Query the history of all fetched data with SQL
+#[derive(Parser, Debug)]
+pub struct Sql {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    /// The query, against the tables `snapshots` and `data_points` or the view `latest`
+    pub query: String,
+
+    /// Format of the result
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+}
+
+/// Show the revisions of past days between two snapshots
+///
+/// Compares the daily increases and the incidence of every day,
+/// the totals are not compared.
+#[derive(Parser, Debug)]
+pub struct Diff {
+    #[clap(flatten)]
+    pub verbosity: Verbosity,
+
+    /// The older snapshot, by file or name as shown by `cache list`, defaults to the second newest
+    #[arg(value_name = "SNAPSHOT")]
+    pub before: Option<String>,
+
+    /// The newer snapshot, defaults to the newest
+    #[arg(value_name = "SNAPSHOT", requires = "before")]
+    pub after: Option<String>,
+}
+
 /// Operation on the cache for the data downloads
 #[derive(Parser, Debug)]
 pub struct Cache {
@@ -100,6 +414,48 @@ pub enum CacheCommand {
     List,
     /// Flushes the cache (deletes all cached files)
     Flush,
+    /// Deletes old snapshots of previous downloads, only the newest 100 are ever kept
+    Prune(Prune),
     /// Refreshes the cache. Download a new file regard less of age.
     Refresh,
+    /// Writes the cached data with its checksum as a JSON bundle, to import it on another machine.
+    /// With `--json` the cached data is written as plain JSON, also when the cache is stored in the binary encoding
+    Export(CacheExport),
+    /// Replaces the cache with the data from a bundle, after validating its checksum
+    Import(CacheImport),
+    /// Describes the cached data and where it came from
+    Info,
+    /// Checks that the cached data is intact, exits with an error if it is not
+    Verify,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CacheExport {
+    /// Write the cached data as plain JSON instead of a bundle
+    #[arg(long)]
+    pub json: bool,
+
+    /// Write to this file instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(ClapArgs, Debug)]
+pub struct CacheImport {
+    /// The bundle that was written by `cache export`
+    #[arg(value_name = "FILE")]
+    pub file: PathBuf,
+}
+
+/// Which snapshots are deleted, a snapshot needs to match all given criteria
+#[derive(ClapArgs, Debug)]
+#[command(group(ArgGroup::new("criteria").required(true).multiple(true).args(["keep", "older_than"])))]
+pub struct Prune {
+    /// Always keep this many of the newest snapshots
+    #[arg(long, value_name = "N")]
+    pub keep: Option<usize>,
+
+    /// Only delete snapshots that are older than this
+    #[arg(long, value_name = "DURATION")]
+    pub older_than: Option<Duration>,
 }
@@ -0,0 +1,311 @@
+//! A compact encoding of the cache, bincode compressed with zstd, that is much
+//! faster to read than the JSON once the cache holds years of data.
+//!
+//! The file starts with [`cache::BINARY_MAGIC`] and the version of the cache,
+//! the same version as of a JSON cache. Caches of older versions are decoded
+//! with their own layout and get the defaults of the migrations.
+
+use crate::{
+    bundle,
+    cache::{Checksums, BINARY_MAGIC},
+    data::{
+        CachedData, CachingData, Cases, DataPoint, Dates, Deaths, Hospitalisations, Recoveries,
+    },
+    migrations::{NewerVersion, VERSION},
+    Result,
+};
+use chrono::{Datelike, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, Error, ErrorKind, Write};
+use tracing::instrument;
+
+/// The default compression level of zstd
+const LEVEL: i32 = 0;
+
+/// The first version that could be written in the binary encoding
+const FIRST_VERSION: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+struct Cache {
+    created_at: i64,
+    population: Option<u32>,
+    /// Over the encoded data points, `None` only for older versions
+    checksum: Option<String>,
+    attributes: Vec<Point>,
+}
+
+/// Version 2, before the population was kept
+#[derive(Deserialize)]
+struct CacheV2 {
+    created_at: i64,
+    attributes: Vec<Point>,
+}
+
+impl From<CacheV2> for Cache {
+    fn from(cache: CacheV2) -> Self {
+        Cache {
+            created_at: cache.created_at,
+            population: None,
+            checksum: None,
+            attributes: cache.attributes,
+        }
+    }
+}
+
+/// Version 3, before the checksum was kept
+#[derive(Deserialize)]
+struct CacheV3 {
+    created_at: i64,
+    population: Option<u32>,
+    attributes: Vec<Point>,
+}
+
+impl From<CacheV3> for Cache {
+    fn from(cache: CacheV3) -> Self {
+        Cache {
+            created_at: cache.created_at,
+            population: cache.population,
+            checksum: None,
+            attributes: cache.attributes,
+        }
+    }
+}
+
+/// A data point with the plain fields that bincode can encode,
+/// the dates are stored as numbers instead of formatted strings
+#[derive(Serialize, Deserialize)]
+struct Point {
+    object_id: u32,
+    date: i32,
+    date_ts: i64,
+    date_range: String,
+    show: bool,
+    incidence: f64,
+    incidence_calculated: f64,
+    cases_total: u32,
+    cases_increase: i32,
+    cases_reported: u32,
+    deaths_total: u32,
+    deaths_increase: i32,
+    recoveries_total: u32,
+    recoveries_increase: i32,
+    hospitalisations_total: u32,
+    hospitalisations_increase: i32,
+    beds_in_use: u32,
+}
+
+impl From<&DataPoint> for Point {
+    fn from(d: &DataPoint) -> Self {
+        Point {
+            object_id: d.object_id,
+            date: d.dates.date.naive_utc().num_days_from_ce(),
+            date_ts: d.dates.date_ts.timestamp_millis(),
+            date_range: d.dates.date_range.clone(),
+            show: d.show,
+            incidence: d.incidence,
+            incidence_calculated: d.incidence_calculated,
+            cases_total: d.cases.total,
+            cases_increase: d.cases.increase,
+            cases_reported: d.cases.reported,
+            deaths_total: d.deaths.total,
+            deaths_increase: d.deaths.increase,
+            recoveries_total: d.recoveries.total,
+            recoveries_increase: d.recoveries.increase,
+            hospitalisations_total: d.hospitalisations.total,
+            hospitalisations_increase: d.hospitalisations.increase,
+            beds_in_use: d.hospitalisations.beds_in_use,
+        }
+    }
+}
+
+impl TryFrom<Point> for DataPoint {
+    type Error = Error;
+
+    fn try_from(p: Point) -> Result<Self, Error> {
+        let date = NaiveDate::from_num_days_from_ce_opt(p.date)
+            .ok_or_else(|| invalid(format!("invalid date {}", p.date)))?;
+        let date_ts = Utc
+            .timestamp_millis_opt(p.date_ts)
+            .single()
+            .ok_or_else(|| invalid(format!("invalid timestamp {}", p.date_ts)))?;
+        Ok(DataPoint {
+            object_id: p.object_id,
+            dates: Dates {
+                date: Utc.from_utc_date(&date),
+                date_ts,
+                date_range: p.date_range,
+            },
+            show: p.show,
+            incidence: p.incidence,
+            incidence_calculated: p.incidence_calculated,
+            cases: Cases {
+                total: p.cases_total,
+                increase: p.cases_increase,
+                reported: p.cases_reported,
+            },
+            deaths: Deaths {
+                total: p.deaths_total,
+                increase: p.deaths_increase,
+            },
+            recoveries: Recoveries {
+                total: p.recoveries_total,
+                increase: p.recoveries_increase,
+            },
+            hospitalisations: Hospitalisations {
+                total: p.hospitalisations_total,
+                increase: p.hospitalisations_increase,
+                beds_in_use: p.beds_in_use,
+            },
+        })
+    }
+}
+
+#[instrument(skip(out, data), err)]
+pub fn write(mut out: impl Write, data: CachingData<'_>) -> Result<()> {
+    out.write_all(BINARY_MAGIC)?;
+    out.write_all(&data.version.to_le_bytes())?;
+
+    let attributes = data.attributes.iter().map(Point::from).collect::<Vec<_>>();
+    let cache = Cache {
+        created_at: data.created_at.timestamp(),
+        population: data.population,
+        checksum: Some(checksum(&attributes)?),
+        attributes,
+    };
+    let mut encoder = zstd::Encoder::new(out, LEVEL)?;
+    bincode::serialize_into(&mut encoder, &cache)?;
+    encoder.finish()?.flush()?;
+    Ok(())
+}
+
+/// Reads a cache, including its header.
+///
+/// A cache that cannot be decoded is reported as an IO error, like a broken file.
+#[instrument(skip(input), err)]
+pub fn read(input: impl BufRead) -> Result<CachedData> {
+    let cache = decode(input)?;
+    Ok(CachedData {
+        created_at: Utc
+            .timestamp_opt(cache.created_at, 0)
+            .single()
+            .ok_or_else(|| invalid(format!("invalid creation time {}", cache.created_at)))?,
+        population: cache.population,
+        attributes: cache
+            .attributes
+            .into_iter()
+            .map(DataPoint::try_from)
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+/// The stored checksum of a cache and the checksum of its data points
+#[instrument(skip(input), err)]
+pub fn checksums(input: impl BufRead) -> Result<Option<Checksums>> {
+    let cache = decode(input)?;
+    let computed = checksum(&cache.attributes)?;
+    Ok(cache.checksum.map(|stored| Checksums { stored, computed }))
+}
+
+fn decode(mut input: impl BufRead) -> Result<Cache> {
+    let mut header = [0; BINARY_MAGIC.len() + 4];
+    input.read_exact(&mut header)?;
+    let mut version = [0; 4];
+    version.copy_from_slice(&header[BINARY_MAGIC.len()..]);
+    let version = u32::from_le_bytes(version);
+    if version > VERSION {
+        return Err(NewerVersion { version }.into());
+    }
+    if version < FIRST_VERSION {
+        return Err(invalid(format!(
+            "the binary cache has the unknown version {}, the first binary version is {}",
+            version, FIRST_VERSION
+        ))
+        .into());
+    }
+
+    let decoder = zstd::Decoder::with_buffer(input)?;
+    let cache = match version {
+        2 => bincode::deserialize_from::<_, CacheV2>(decoder).map(Cache::from),
+        3 => bincode::deserialize_from::<_, CacheV3>(decoder).map(Cache::from),
+        _ => bincode::deserialize_from::<_, Cache>(decoder),
+    }
+    .map_err(invalid)?;
+    Ok(cache)
+}
+
+fn checksum(attributes: &[Point]) -> Result<String> {
+    Ok(bundle::checksum(&bincode::serialize(attributes)?))
+}
+
+fn invalid(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> Error {
+    Error::new(ErrorKind::InvalidData, e)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(day: i32) -> Point {
+        Point {
+            object_id: day as u32,
+            date: NaiveDate::from_ymd(2021, 11, 20).num_days_from_ce() + day,
+            date_ts: 0,
+            date_range: String::new(),
+            show: false,
+            incidence: 100.0,
+            incidence_calculated: 99.0,
+            cases_total: 1_000,
+            cases_increase: 10,
+            cases_reported: 9,
+            deaths_total: 5,
+            deaths_increase: 0,
+            recoveries_total: 900,
+            recoveries_increase: 8,
+            hospitalisations_total: 50,
+            hospitalisations_increase: 1,
+            beds_in_use: 20,
+        }
+    }
+
+    #[derive(Serialize)]
+    struct WrittenV2 {
+        created_at: i64,
+        attributes: Vec<Point>,
+    }
+
+    fn written_v2(created_at: i64) -> Vec<u8> {
+        let mut file = Vec::new();
+        file.extend_from_slice(BINARY_MAGIC);
+        file.extend_from_slice(&2_u32.to_le_bytes());
+        let mut encoder = zstd::Encoder::new(&mut file, LEVEL).unwrap();
+        let cache = WrittenV2 {
+            created_at,
+            attributes: vec![point(0), point(1)],
+        };
+        bincode::serialize_into(&mut encoder, &cache).unwrap();
+        encoder.finish().unwrap();
+        file
+    }
+
+    #[test]
+    fn version_2_is_read_without_population() {
+        let file = written_v2(1_637_395_200);
+
+        let read = read(file.as_slice()).unwrap();
+
+        assert_eq!(read.created_at, Utc.timestamp(1_637_395_200, 0));
+        assert_eq!(read.population, None);
+        assert_eq!(read.attributes.len(), 2);
+        assert_eq!(read.attributes[1].cases.reported, 9);
+    }
+
+    #[test]
+    fn invalid_creation_time_is_invalid_data() {
+        let file = written_v2(i64::MAX);
+
+        let error = read(file.as_slice()).unwrap_err();
+
+        let error = error.downcast_ref::<Error>().unwrap();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,220 @@
+//! A self-describing file with the cached data, to move the exact data that
+//! was looked at to machines that cannot download it.
+//!
+//! The cache is embedded as it is written in JSON, together with a checksum
+//! over exactly those bytes, so that it is validated before it is imported.
+
+use crate::{
+    cache,
+    data::{CachedData, CachingData},
+    migrations, Result,
+};
+use chrono::{DateTime, Utc};
+use color_eyre::{eyre::eyre, Help};
+use serde::{Deserialize, Serialize};
+use serde_json::{error::Category, value::RawValue};
+use sha2::{Digest, Sha256};
+use std::io::{Read, Write};
+use tracing::{debug, instrument};
+
+static FORMAT: &str = "covidd-bundle";
+static EXPORTED_BY: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+static CHECKSUM_ALGORITHM: &str = "sha256";
+
+/// The version of the bundle, the cache in it has its own version
+const VERSION: u32 = 1;
+
+/// Only the format is read first, to tell other files apart from damaged bundles
+#[derive(Deserialize)]
+struct Header {
+    format: String,
+}
+
+/// Only `format`, `version`, `checksum` and `cache` are read, the rest describes the bundle to humans
+#[derive(Serialize, Deserialize)]
+struct Bundle {
+    format: String,
+    version: u32,
+    #[serde(default)]
+    exported_by: String,
+    exported_at: Option<DateTime<Utc>>,
+    fetched_at: Option<DateTime<Utc>>,
+    population: Option<u32>,
+    #[serde(default)]
+    data_points: usize,
+    checksum: String,
+    cache: Box<RawValue>,
+}
+
+/// Writes the current cache as a bundle
+#[instrument(skip(out), err)]
+pub fn export(out: impl Write) -> Result<()> {
+    let data = cache::get_cached_data()?.ok_or_else(|| {
+        eyre!("There is no cached data to export")
+            .suggestion("Run the `cache refresh` subcommand to download the data.")
+    })?;
+    write(out, &data)
+}
+
+/// Reads and validates a bundle, the data is not yet imported into the cache
+#[instrument(skip(input), err)]
+pub fn import(mut input: impl Read) -> Result<CachedData> {
+    let mut bundle = Vec::new();
+    input.read_to_end(&mut bundle)?;
+    read(&bundle)
+}
+
+fn write(mut out: impl Write, data: &CachedData) -> Result<()> {
+    let cache = serde_json::to_string(&CachingData {
+        version: migrations::VERSION,
+        created_at: data.created_at,
+        population: data.population,
+        attributes: &data.attributes,
+    })?;
+    let bundle = Bundle {
+        format: String::from(FORMAT),
+        version: VERSION,
+        exported_by: String::from(EXPORTED_BY),
+        exported_at: Some(Utc::now()),
+        fetched_at: Some(data.created_at),
+        population: data.population,
+        data_points: data.attributes.len(),
+        checksum: checksum(cache.as_bytes()),
+        cache: RawValue::from_string(cache)?,
+    };
+    serde_json::to_writer_pretty(&mut out, &bundle)?;
+    writeln!(out)?;
+    out.flush()?;
+    debug!("Exported {} data points", bundle.data_points);
+    Ok(())
+}
+
+fn read(bundle: &[u8]) -> Result<CachedData> {
+    let is_bundle = match serde_json::from_slice::<Header>(bundle) {
+        Ok(header) => header.format == FORMAT,
+        Err(e) if e.classify() == Category::Data => false,
+        Err(e) => return Err(damaged(e)),
+    };
+    if !is_bundle {
+        return Err(eyre!("The file is not a covidd bundle")
+            .suggestion("Create the bundle with the `cache export` subcommand."));
+    }
+    let bundle = serde_json::from_slice::<Bundle>(bundle).map_err(damaged)?;
+    if bundle.version > VERSION {
+        return Err(eyre!(
+            "The bundle has version {}, but this covidd only supports up to version {}",
+            bundle.version,
+            VERSION
+        )
+        .suggestion("Update covidd to import this bundle."));
+    }
+    debug!("Reading a bundle exported by {}", bundle.exported_by);
+
+    let cache = bundle.cache.get();
+    if checksum(cache.as_bytes()) != bundle.checksum {
+        return Err(
+            eyre!("The bundle is damaged, its checksum does not match the data")
+                .suggestion("Export the bundle again and make sure that it is copied completely."),
+        );
+    }
+    cache::from_json(serde_json::from_str(cache)?)
+}
+
+fn damaged(e: serde_json::Error) -> color_eyre::Report {
+    eyre!("The bundle is damaged, it could not be read: {}", e)
+        .suggestion("Export the bundle again and make sure that it is copied completely.")
+}
+
+/// A hash of the data, prefixed with the algorithm
+pub fn checksum(data: &[u8]) -> String {
+    let hash = Sha256::digest(data);
+    let hex = hash
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>();
+    format!("{}:{}", CHECKSUM_ALGORITHM, hex)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{Cases, DataPoint, Dates, Deaths, Hospitalisations, Recoveries};
+    use chrono::TimeZone;
+
+    fn data() -> CachedData {
+        let date = Utc.ymd(2022, 3, 1);
+        CachedData {
+            created_at: Utc.ymd(2022, 3, 2).and_hms(8, 30, 0),
+            population: Some(556_780),
+            attributes: vec![DataPoint {
+                object_id: 1,
+                dates: Dates {
+                    date,
+                    date_ts: date.and_hms(12, 0, 0),
+                    date_range: String::new(),
+                },
+                show: true,
+                incidence: 1234.5,
+                incidence_calculated: 1230.1,
+                cases: Cases {
+                    total: 150_000,
+                    increase: 2_500,
+                    reported: 2_400,
+                },
+                deaths: Deaths {
+                    total: 1_200,
+                    increase: 3,
+                },
+                recoveries: Recoveries {
+                    total: 140_000,
+                    increase: 2_000,
+                },
+                hospitalisations: Hospitalisations {
+                    total: 5_000,
+                    increase: 20,
+                    beds_in_use: 150,
+                },
+            }],
+        }
+    }
+
+    fn bundle() -> String {
+        let mut bundle = Vec::new();
+        write(&mut bundle, &data()).unwrap();
+        String::from_utf8(bundle).unwrap()
+    }
+
+    #[test]
+    fn bundle_keeps_the_data_and_its_metadata() {
+        let read = read(bundle().as_bytes()).unwrap();
+
+        assert_eq!(read.created_at, data().created_at);
+        assert_eq!(read.population, Some(556_780));
+        assert_eq!(
+            serde_json::to_value(&read.attributes).unwrap(),
+            serde_json::to_value(&data().attributes).unwrap()
+        );
+    }
+
+    #[test]
+    fn changed_data_is_rejected() {
+        let bundle = bundle().replace("150000", "150001");
+
+        let error = read(bundle.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("checksum"));
+    }
+
+    #[test]
+    fn truncated_bundle_is_damaged() {
+        let bundle = bundle();
+
+        let error = read(&bundle.as_bytes()[..bundle.len() / 2]).unwrap_err();
+        assert!(error.to_string().contains("damaged"));
+    }
+
+    #[test]
+    fn other_files_are_rejected() {
+        let error = read(br#"{"version": 3, "attributes": []}"#).unwrap_err();
+        assert!(error.to_string().contains("not a covidd bundle"));
+    }
+}
@@ -1,18 +1,110 @@
 use crate::{
+    bundle,
     data::{CachedData, CachingData, DataRef},
+    migrations::{self, NewerVersion},
     Result,
 };
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use color_eyre::{eyre::eyre, Help};
 use directories::ProjectDirs;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{value::RawValue, Value};
 use std::{
     fmt::Debug,
-    fs::File,
-    io::ErrorKind,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, ErrorKind, Read, Write},
     path::{Path, PathBuf},
 };
-use tracing::{instrument, trace, warn};
+use tracing::{debug, instrument, trace, warn};
 
 static APPLICATION: &str = env!("CARGO_PKG_NAME");
 static CACHE_FILE: &str = "cached_data.json";
+static SNAPSHOT_DIR: &str = "snapshots";
+static LOCK_FILE: &str = "download.lock";
+static PORTABLE_DIR: &str = "covidd-cache";
+
+static LOCATION: OnceCell<Location> = OnceCell::new();
+
+/// Where the cache is kept
+#[derive(Debug, Clone)]
+pub enum Location {
+    /// The user's cache directory of the platform
+    Default,
+    Dir(PathBuf),
+    /// Nothing is read from or written to a cache
+    Disabled,
+}
+
+/// Sets the location for all later cache operations, can only be called once
+pub fn set_location(location: Location) {
+    debug!("Using cache location {:?}", location);
+    if LOCATION.set(location).is_err() {
+        warn!("The cache location was already set and is not changed");
+    }
+}
+
+/// A directory next to the covidd binary, to run it e.g. from a USB stick
+pub fn portable_dir() -> Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    let dir = exe.parent().ok_or_else(|| {
+        eyre!(
+            "Could not find the directory of the binary at [{}]",
+            exe.display()
+        )
+    })?;
+    Ok(dir.join(PORTABLE_DIR))
+}
+/// Snapshots of the same second are told apart by the fraction of the second,
+/// older snapshots without the fraction are still read
+static SNAPSHOT_FORMAT: &str = "%Y%m%dT%H%M%S%.fZ";
+/// How many snapshots are kept when the cache is written, older ones are pruned
+const MAX_SNAPSHOTS: usize = 100;
+
+/// The start of a cache in the binary encoding, a JSON cache can never start like this.
+///
+/// The file names are kept for either encoding, so that the cache is found after a rebuild.
+pub const BINARY_MAGIC: &[u8] = b"covidd\0b";
+
+/// The checksum that was stored when the cache was written, and the one of its data now
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checksums {
+    pub stored: String,
+    pub computed: String,
+}
+
+/// The envelope of a JSON cache, the checksum is over exactly the bytes of the attributes
+#[derive(Serialize)]
+struct JsonCache<'a> {
+    version: u32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    created_at: DateTime<Utc>,
+    population: Option<u32>,
+    checksum: String,
+    attributes: &'a RawValue,
+}
+
+#[derive(Deserialize)]
+struct JsonChecksum<'a> {
+    checksum: Option<String>,
+    #[serde(borrow)]
+    attributes: &'a RawValue,
+}
+
+/// Held while new data is downloaded, so that concurrent processes wait for that data
+#[derive(Debug)]
+pub struct DownloadLock {
+    _file: Option<File>,
+    /// Whether another process was downloading while this lock was acquired
+    pub waited: bool,
+}
+
+/// A previously fetched dataset that is kept around after a refresh
+#[derive(Debug)]
+pub struct Snapshot {
+    pub file: PathBuf,
+    pub created_at: DateTime<Utc>,
+}
 
 pub fn get_cached_data() -> Result<Option<CachedData>> {
     get_cached().map(|c| c.map(|(_, d)| d))
@@ -39,7 +131,7 @@ pub fn get_cached() -> Result<Option<(PathBuf, CachedData)>> {
                         warn!("Could not get permission to read the cached data at [{0}]. While this is not an error, it is recommended to investigate the reason, as the cache could otherwise not be used. It is most likely a file permission issue, please make sure that your current user can read [{0}].", cache_file.display());
                         Ok(None)
                     }
-                    ErrorKind::Other => {
+                    ErrorKind::Other | ErrorKind::InvalidData => {
                         warn!("Could not read the cached data at [{}]. While this is not an error, it is recommended to investigate the reason, as the cache could otherwise not be used. Error: [{}].", cache_file.display(), ioe);
                         Ok(None)
                     }
@@ -49,6 +141,9 @@ pub fn get_cached() -> Result<Option<(PathBuf, CachedData)>> {
                     }
                     _ => Err(e),
                 }
+            } else if let Some(nv) = e.downcast_ref::<NewerVersion>() {
+                warn!("Could not use the cached data at [{}] as it was written by a newer version of covidd. Update covidd to use the cache again, otherwise it will be replaced with data in the current format. Error: [{}].", cache_file.display(), nv);
+                Ok(None)
             } else if let Some(sje) = e.downcast_ref::<serde_json::error::Error>() {
                 warn!("Could not parse the cached data at [{}]. While this is not an error, it is recommended to investigate the reason, as the cache could otherwise not be used. Error: [{}].", cache_file.display(), sje);
                 Ok(None)
@@ -60,14 +155,20 @@ pub fn get_cached() -> Result<Option<(PathBuf, CachedData)>> {
 }
 
 #[instrument(skip(data))]
-pub fn store_data(data: DataRef<'_>) -> Result<()> {
+pub fn store_data(data: DataRef<'_>, population: u32, created_at: DateTime<Utc>) -> Result<()> {
     let cache_file = match cache_file() {
         None => return Ok(()),
         Some(file) => file,
     };
     trace!("cache file {}", cache_file.display());
 
-    match write_to_file(&cache_file, data) {
+    let data = CachingData {
+        version: migrations::VERSION,
+        created_at,
+        population: Some(population),
+        attributes: data,
+    };
+    match write_with_snapshot(&cache_file, data) {
         Ok(()) => Ok(()),
         Err(e) => {
             if let Some(ioe) = e.downcast_ref::<std::io::Error>() {
@@ -104,7 +205,9 @@ pub fn remove_cache() -> Result<()> {
     };
     trace!("cache file {}", cache_file.display());
 
-    match remove_file(&cache_file) {
+    // the snapshots are removed even if the cache file is already gone
+    let removed_snapshots = remove_snapshots(&cache_file);
+    match remove_file(&cache_file).and(removed_snapshots) {
         Ok(()) => Ok(()),
         Err(e) => {
             if let Some(ioe) = e.downcast_ref::<std::io::Error>() {
@@ -134,13 +237,286 @@ pub fn remove_cache() -> Result<()> {
     }
 }
 
-fn cache_file() -> Option<PathBuf> {
-    let dirs = ProjectDirs::from("de", "knutwalker", APPLICATION)?;
-    let mut file = dirs.cache_dir().to_path_buf();
+/// Writes the cached data as JSON, regardless of its encoding
+#[instrument(skip(out), err)]
+pub fn export_json(mut out: impl Write) -> Result<()> {
+    let data = get_cached_data()?.ok_or_else(|| {
+        eyre!("There is no cached data")
+            .suggestion("Run the `cache refresh` subcommand to download the data.")
+    })?;
+    let data = CachingData {
+        version: migrations::VERSION,
+        created_at: data.created_at,
+        population: data.population,
+        attributes: &data.attributes,
+    };
+    write_json(&mut out, data)?;
+    writeln!(out)?;
+    out.flush()?;
+    Ok(())
+}
+
+/// Replaces the cache with data that was fetched elsewhere, keeping when it was fetched.
+///
+/// Unlike a download, the data cannot be fetched again, so failures are errors.
+#[instrument(skip(data), err)]
+pub fn store_imported(data: &CachedData) -> Result<PathBuf> {
+    let cache_file = cache_file().ok_or_else(|| {
+        eyre!("There is no cache to import the data into")
+            .suggestion("Remove `--no-cache` to import the data.")
+    })?;
+    trace!("cache file {}", cache_file.display());
+
+    let caching = CachingData {
+        version: migrations::VERSION,
+        created_at: data.created_at,
+        population: data.population,
+        attributes: &data.attributes,
+    };
+    write_with_snapshot(&cache_file, caching)?;
+    Ok(cache_file)
+}
+
+/// Reads a cache of any version from its JSON
+pub fn from_json(data: Value) -> Result<CachedData> {
+    Ok(serde_json::from_value(migrations::migrate(data)?)?)
+}
+
+/// All snapshots, the newest first
+#[instrument]
+pub fn snapshots() -> Result<Vec<Snapshot>> {
+    let dir = match cache_file() {
+        None => return Ok(Vec::new()),
+        Some(file) => snapshot_dir(&file),
+    };
+    trace!("snapshot dir {}", dir.display());
+    snapshots_in(&dir)
+}
+
+fn snapshots_in(dir: &Path) -> Result<Vec<Snapshot>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut snapshots = Vec::new();
+    for entry in entries {
+        let file = entry?.path();
+        let created_at = file
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| Utc.datetime_from_str(stem, SNAPSHOT_FORMAT).ok());
+        match created_at {
+            Some(created_at) => snapshots.push(Snapshot { file, created_at }),
+            None => debug!("Ignoring unknown file {} in the snapshots", file.display()),
+        }
+    }
+    snapshots.sort_by_key(|s| std::cmp::Reverse(s.created_at));
+
+    Ok(snapshots)
+}
+
+/// Finds a snapshot by its file or by the start of its name, the newest one if several match
+#[instrument]
+pub fn find_snapshot(name: &str) -> Result<Option<Snapshot>> {
+    let file = Path::new(name);
+    Ok(snapshots()?.into_iter().find(|s| {
+        s.file == file
+            || s.file
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| stem.starts_with(name))
+    }))
+}
+
+pub fn read_snapshot(snapshot: &Snapshot) -> Result<CachedData> {
+    read_from_file(&snapshot.file)
+}
+
+/// Reads a cache file, unlike [`get_cached`] every problem is an error
+pub fn read_cache_file(file: &Path) -> Result<CachedData> {
+    read_from_file(file)
+}
+
+/// The checksums of the data in a cache file, `None` if it was written without a checksum
+#[instrument(err)]
+pub fn checksums(file: &Path) -> Result<Option<Checksums>> {
+    let file = try_lock_file_for_reading(File::open(file)?)?;
+    let mut reader = BufReader::new(file);
+    if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+        return binary_checksums(reader);
+    }
+
+    let mut json = Vec::new();
+    reader.read_to_end(&mut json)?;
+    let cache = serde_json::from_slice::<JsonChecksum<'_>>(&json)?;
+    Ok(cache.checksum.map(|stored| Checksums {
+        stored,
+        computed: bundle::checksum(cache.attributes.get().as_bytes()),
+    }))
+}
+
+/// Whether the cache file is in the binary encoding
+pub fn is_binary(file: &Path) -> Result<bool> {
+    let mut reader = BufReader::new(File::open(file)?);
+    Ok(reader.fill_buf()?.starts_with(BINARY_MAGIC))
+}
+
+/// The newest data that was fetched on or before the given date
+#[instrument]
+pub fn get_cached_as_of(date: NaiveDate) -> Result<Option<CachedData>> {
+    let snapshot = snapshots()?
+        .into_iter()
+        .find(|s| s.created_at.date().naive_utc() <= date);
+    if let Some(snapshot) = snapshot {
+        debug!("Using the snapshot from {}", snapshot.created_at);
+        return read_snapshot(&snapshot).map(Some);
+    }
+
+    // the cache might predate the snapshots
+    Ok(get_cached_data()?.filter(|data| data.created_at.date().naive_utc() <= date))
+}
+
+/// Removes all snapshots that are not among the newest `keep` ones and older than `older_than`
+#[instrument]
+pub fn prune_snapshots(keep: Option<usize>, older_than: Option<Duration>) -> Result<Vec<Snapshot>> {
+    match cache_file() {
+        None => Ok(Vec::new()),
+        Some(file) => prune_snapshots_in(&snapshot_dir(&file), keep, older_than),
+    }
+}
+
+fn prune_snapshots_in(
+    dir: &Path,
+    keep: Option<usize>,
+    older_than: Option<Duration>,
+) -> Result<Vec<Snapshot>> {
+    let now = Utc::now();
+    let keep = keep.unwrap_or(0);
+    let mut pruned = Vec::new();
+    for snapshot in snapshots_in(dir)?.into_iter().skip(keep) {
+        if older_than.map_or(true, |age| now - snapshot.created_at > age) {
+            remove_file(&snapshot.file)?;
+            pruned.push(snapshot);
+        }
+    }
+    Ok(pruned)
+}
+
+/// Waits until no other process is downloading, or until the timeout runs out.
+///
+/// The lock is not required to download, so failures are only logged.
+#[instrument]
+pub fn lock_download(timeout: std::time::Duration) -> DownloadLock {
+    let lock_file = match cache_dir() {
+        None => {
+            return DownloadLock {
+                _file: None,
+                waited: false,
+            }
+        }
+        Some(dir) => dir.join(LOCK_FILE),
+    };
+    trace!("lock file {}", lock_file.display());
+
+    match wait_for_lock(&lock_file, timeout) {
+        Ok((file, waited)) => DownloadLock {
+            _file: file,
+            waited,
+        },
+        Err(e) => {
+            warn!("Could not use the lock file at [{}]. While this is not an error, it is recommended to investigate the reason, as concurrent processes could otherwise not share their downloads. Error: [{}].", lock_file.display(), e);
+            DownloadLock {
+                _file: None,
+                waited: false,
+            }
+        }
+    }
+}
+
+fn wait_for_lock(lock_file: &Path, timeout: std::time::Duration) -> Result<(Option<File>, bool)> {
+    use fs2::FileExt;
+    const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+    if let Some(parent) = lock_file.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_file)?;
+
+    let start = std::time::Instant::now();
+    let mut waited = false;
+    loop {
+        match file.try_lock_exclusive() {
+            Ok(()) => return Ok((Some(file), waited)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                if !waited {
+                    debug!("Waiting for another process to download the data");
+                    waited = true;
+                }
+                if start.elapsed() >= timeout {
+                    warn!("Another process has been downloading the data for longer than {}, downloading it again.", humantime::format_duration(timeout));
+                    return Ok((None, true));
+                }
+                std::thread::sleep(POLL_INTERVAL);
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+pub fn cache_dir() -> Option<PathBuf> {
+    match LOCATION.get().unwrap_or(&Location::Default) {
+        Location::Default => {
+            let dirs = ProjectDirs::from("de", "knutwalker", APPLICATION)?;
+            Some(dirs.cache_dir().to_path_buf())
+        }
+        Location::Dir(dir) => Some(dir.clone()),
+        Location::Disabled => None,
+    }
+}
+
+pub fn cache_file() -> Option<PathBuf> {
+    let mut file = cache_dir()?;
     file.push(CACHE_FILE);
     Some(file)
 }
 
+fn snapshot_dir(cache_file: &Path) -> PathBuf {
+    cache_file.with_file_name(SNAPSHOT_DIR)
+}
+
+#[instrument(err)]
+fn keep_snapshot(cache_file: &Path, created_at: DateTime<Utc>) -> Result<()> {
+    let dir = snapshot_dir(cache_file);
+    std::fs::create_dir_all(&dir)?;
+    let extension = if is_binary(cache_file)? {
+        "bin"
+    } else {
+        "json"
+    };
+    let file = dir.join(format!(
+        "{}.{}",
+        created_at.format(SNAPSHOT_FORMAT),
+        extension
+    ));
+    std::fs::copy(cache_file, &file)?;
+    debug!("Kept a snapshot at {}", file.display());
+    Ok(())
+}
+
+#[instrument(err)]
+fn remove_snapshots(cache_file: &Path) -> Result<()> {
+    match std::fs::remove_dir_all(snapshot_dir(cache_file)) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
 #[instrument(err)]
 fn read_from_file(file: impl AsRef<Path> + Debug) -> Result<CachedData> {
     let file = file.as_ref();
@@ -150,25 +526,134 @@ fn read_from_file(file: impl AsRef<Path> + Debug) -> Result<CachedData> {
 }
 
 fn read_from_open_file(file: File) -> Result<CachedData> {
-    let data: CachedData = serde_json::from_reader(file)?;
-    Ok(data)
+    let mut reader = BufReader::new(file);
+    if reader.fill_buf()?.starts_with(BINARY_MAGIC) {
+        return read_binary(reader);
+    }
+    read_json(reader)
+}
+
+fn read_json(reader: impl BufRead) -> Result<CachedData> {
+    from_json(serde_json::from_reader(reader)?)
+}
+
+#[cfg(feature = "binary-cache")]
+fn read_binary(reader: impl BufRead) -> Result<CachedData> {
+    crate::binary::read(reader)
+}
+
+#[cfg(feature = "binary-cache")]
+fn binary_checksums(reader: impl BufRead) -> Result<Option<Checksums>> {
+    crate::binary::checksums(reader)
+}
+
+#[cfg(not(feature = "binary-cache"))]
+fn read_binary(_: impl BufRead) -> Result<CachedData> {
+    Err(without_binary_support())
+}
+
+#[cfg(not(feature = "binary-cache"))]
+fn binary_checksums(_: impl BufRead) -> Result<Option<Checksums>> {
+    Err(without_binary_support())
+}
+
+#[cfg(not(feature = "binary-cache"))]
+fn without_binary_support() -> color_eyre::Report {
+    std::io::Error::new(
+        ErrorKind::Other,
+        "the cache is in the binary encoding, but covidd was built without binary cache support",
+    )
+    .into()
+}
+
+/// Writes the cache and keeps a copy as a snapshot, which is not required to succeed.
+///
+/// Only the newest [`MAX_SNAPSHOTS`] snapshots are kept.
+fn write_with_snapshot(cache_file: &Path, data: CachingData<'_>) -> Result<()> {
+    let created_at = data.created_at;
+    write_to_file(cache_file, data)?;
+    if let Err(e) = keep_snapshot(cache_file, created_at) {
+        warn!("Could not keep a snapshot of the cached data at [{}]. While this is not an error, it is recommended to investigate the reason, as the history of the data could otherwise not be used. Error: [{}].", cache_file.display(), e);
+    }
+    let dir = snapshot_dir(cache_file);
+    if let Err(e) = prune_snapshots_in(&dir, Some(MAX_SNAPSHOTS), None) {
+        warn!("Could not prune the snapshots at [{}]. While this is not an error, it is recommended to investigate the reason, as the snapshots could otherwise fill up the disk. Error: [{}].", dir.display(), e);
+    }
+    Ok(())
 }
 
 #[instrument(skip(data), err)]
-fn write_to_file(file: impl AsRef<Path> + Debug, data: DataRef<'_>) -> Result<()> {
+fn write_to_file(file: impl AsRef<Path> + Debug, data: CachingData<'_>) -> Result<()> {
     let file = file.as_ref();
     if let Some(parent) = file.parent() {
         if !parent.exists() {
             std::fs::create_dir_all(parent)?;
         }
     }
-    let file = File::create(file)?;
-    let file = try_lock_file_for_writing(file)?;
-    let data = CachingData {
-        created_at: chrono::Utc::now(),
-        attributes: data,
-    };
-    write_to_open_file(file, data)
+    write_atomically(file, |out| write_to_open_file(out, data))
+}
+
+/// Writes to a temporary file next to `file` and renames it over `file`
+/// while holding the lock on it, so that readers only ever see a complete cache
+fn write_atomically(
+    file: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let temp = TempFile::next_to(file);
+    trace!("temp file {}", temp.0.display());
+
+    write_temp_file(&temp.0, write)?;
+    replace_file(&temp.0, file)
+}
+
+/// Removed when it is dropped, unless it was renamed over the cache,
+/// so that neither an error nor a panic leaves it behind
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn next_to(file: &Path) -> Self {
+        let name = file
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or(CACHE_FILE);
+        TempFile(file.with_file_name(format!(".{}.{}.tmp", name, std::process::id())))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+fn write_temp_file(
+    temp: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> Result<()>,
+) -> Result<()> {
+    let mut out = BufWriter::new(File::create(temp)?);
+    write(&mut out)?;
+    let file = out.into_inner().map_err(|e| e.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
+
+fn replace_file(temp: &Path, file: &Path) -> Result<()> {
+    // must not truncate, readers of the current cache might still be holding it
+    let lock = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(file)?;
+    let _lock = try_lock_file_for_writing(lock)?;
+    std::fs::rename(temp, file)?;
+
+    // persist the rename, this is not supported on every platform
+    if let Some(dir) = file.parent() {
+        if let Ok(dir) = File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    Ok(())
 }
 
 #[instrument(err)]
@@ -176,8 +661,26 @@ fn remove_file(file: impl AsRef<Path> + Debug) -> Result<()> {
     Ok(std::fs::remove_file(file)?)
 }
 
-fn write_to_open_file(file: File, data: CachingData<'_>) -> Result<()> {
-    serde_json::to_writer_pretty(file, &data)?;
+#[cfg(feature = "binary-cache")]
+fn write_to_open_file(out: impl Write, data: CachingData<'_>) -> Result<()> {
+    crate::binary::write(out, data)
+}
+
+#[cfg(not(feature = "binary-cache"))]
+fn write_to_open_file(out: impl Write, data: CachingData<'_>) -> Result<()> {
+    write_json(out, data)
+}
+
+fn write_json(out: impl Write, data: CachingData<'_>) -> Result<()> {
+    let attributes = RawValue::from_string(serde_json::to_string_pretty(data.attributes)?)?;
+    let cache = JsonCache {
+        version: data.version,
+        created_at: data.created_at,
+        population: data.population,
+        checksum: bundle::checksum(attributes.get().as_bytes()),
+        attributes: &attributes,
+    };
+    serde_json::to_writer_pretty(out, &cache)?;
     Ok(())
 }
 
@@ -192,3 +695,295 @@ fn try_lock_file_for_reading(file: File) -> Result<File> {
     file.try_lock_shared()?;
     Ok(file)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("covidd-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut files = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn interrupted_write_keeps_the_old_cache() {
+        let dir = test_dir("interrupted");
+        let file = dir.join(CACHE_FILE);
+        write_atomically(&file, |out| Ok(out.write_all(b"old")?)).unwrap();
+
+        let result = write_atomically(&file, |out| {
+            out.write_all(b"partial")?;
+            out.flush()?;
+            Err(color_eyre::eyre::eyre!("interrupted"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        assert_eq!(files_in(&dir), vec![CACHE_FILE]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn panicking_write_leaves_no_temp_file() {
+        let dir = test_dir("panicking");
+        let file = dir.join(CACHE_FILE);
+        write_atomically(&file, |out| Ok(out.write_all(b"old")?)).unwrap();
+
+        let result = std::panic::catch_unwind(|| {
+            write_atomically(&file, |out| {
+                out.write_all(b"partial")?;
+                out.flush()?;
+                panic!("interrupted")
+            })
+        });
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        assert_eq!(files_in(&dir), vec![CACHE_FILE]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn locked_cache_is_not_replaced() {
+        let dir = test_dir("locked");
+        let file = dir.join(CACHE_FILE);
+        write_atomically(&file, |out| Ok(out.write_all(b"old")?)).unwrap();
+
+        let reader = try_lock_file_for_reading(File::open(&file).unwrap()).unwrap();
+        let result = write_atomically(&file, |out| Ok(out.write_all(b"new")?));
+        drop(reader);
+
+        assert!(result.is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        assert_eq!(files_in(&dir), vec![CACHE_FILE]);
+
+        write_atomically(&file, |out| Ok(out.write_all(b"new")?)).unwrap();
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn years_of_data(years: i64) -> Vec<crate::data::DataPoint> {
+        use crate::data::{Cases, DataPoint, Dates, Deaths, Hospitalisations, Recoveries};
+
+        let start = Utc.ymd(2020, 3, 1);
+        (0..years * 365)
+            .map(|day| {
+                let date = start + Duration::days(day);
+                let n = day as u32;
+                DataPoint {
+                    object_id: n,
+                    dates: Dates {
+                        date,
+                        date_ts: date.and_hms(12, 0, 0),
+                        date_range: date.format("%d.%m.%Y").to_string(),
+                    },
+                    show: day % 7 == 0,
+                    incidence: f64::from(n % 500) * 1.5,
+                    incidence_calculated: f64::from(n % 500) * 1.4,
+                    cases: Cases {
+                        total: n * 100,
+                        increase: 100,
+                        reported: 90 + n % 20,
+                    },
+                    deaths: Deaths {
+                        total: n,
+                        increase: 1,
+                    },
+                    recoveries: Recoveries {
+                        total: n * 90,
+                        increase: 90,
+                    },
+                    hospitalisations: Hospitalisations {
+                        total: n * 3,
+                        increase: -2,
+                        beds_in_use: n % 300,
+                    },
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn json_cache_keeps_the_checksum_of_its_data() {
+        let dir = test_dir("json-checksum");
+        let file = dir.join(CACHE_FILE);
+        let data = years_of_data(1);
+        let created_at = Utc::now();
+        write_atomically(&file, |out| {
+            write_json(out, caching_data(&data, created_at))
+        })
+        .unwrap();
+
+        let written = checksums(&file).unwrap().unwrap();
+        assert_eq!(written.stored, written.computed);
+
+        let changed = fs::read_to_string(&file).unwrap().replacen(
+            "\"Fallzahl\": 100,",
+            "\"Fallzahl\": 101,",
+            1,
+        );
+        fs::write(&file, changed).unwrap();
+        let changed = checksums(&file).unwrap().unwrap();
+        assert_ne!(changed.stored, changed.computed);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn older_cache_has_no_checksum() {
+        let dir = test_dir("no-checksum");
+        let file = dir.join(CACHE_FILE);
+        fs::write(
+            &file,
+            r#"{"version": 3, "created_at": 0, "attributes": []}"#,
+        )
+        .unwrap();
+
+        assert_eq!(checksums(&file).unwrap(), None);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "binary-cache")]
+    #[test]
+    fn binary_cache_keeps_the_checksum_of_its_data() {
+        let dir = test_dir("binary-checksum");
+        let file = dir.join(CACHE_FILE);
+        let data = years_of_data(1);
+        write_to_file(&file, caching_data(&data, Utc::now())).unwrap();
+
+        let written = checksums(&file).unwrap().unwrap();
+        assert_eq!(written.stored, written.computed);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "binary-cache")]
+    #[test]
+    fn binary_cache_keeps_all_values() {
+        let dir = test_dir("binary");
+        let file = dir.join(CACHE_FILE);
+        let data = years_of_data(1);
+        let caching = CachingData {
+            version: migrations::VERSION,
+            created_at: Utc::now(),
+            population: Some(556_780),
+            attributes: &data,
+        };
+        write_to_file(&file, caching).unwrap();
+
+        assert!(fs::read(&file).unwrap().starts_with(BINARY_MAGIC));
+        let read = read_from_file(&file).unwrap();
+        assert_eq!(read.population, Some(556_780));
+        assert_eq!(
+            serde_json::to_value(&read.attributes).unwrap(),
+            serde_json::to_value(&data).unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "binary-cache")]
+    #[test]
+    fn binary_snapshots_are_named_after_their_encoding() {
+        let dir = test_dir("binary-snapshot");
+        let file = dir.join(CACHE_FILE);
+        let data = years_of_data(1);
+        let created_at = Utc.ymd(2021, 11, 20).and_hms(8, 0, 0);
+        write_with_snapshot(&file, caching_data(&data, created_at)).unwrap();
+
+        let snapshots = snapshots_in(&snapshot_dir(&file)).unwrap();
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].file.extension().unwrap(), "bin");
+        assert_eq!(read_snapshot(&snapshots[0]).unwrap().created_at, created_at);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "binary-cache")]
+    #[test]
+    fn json_cache_is_still_read() {
+        let dir = test_dir("json");
+        let file = dir.join(CACHE_FILE);
+        let data = years_of_data(1);
+        let caching = CachingData {
+            version: migrations::VERSION,
+            created_at: Utc::now(),
+            population: None,
+            attributes: &data,
+        };
+        write_atomically(&file, |out| write_json(out, caching)).unwrap();
+
+        let read = read_from_file(&file).unwrap();
+        assert_eq!(read.attributes.len(), data.len());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn caching_data(data: &[crate::data::DataPoint], created_at: DateTime<Utc>) -> CachingData<'_> {
+        CachingData {
+            version: migrations::VERSION,
+            created_at,
+            population: None,
+            attributes: data,
+        }
+    }
+
+    #[test]
+    fn snapshots_of_the_same_second_are_kept() {
+        let dir = test_dir("same-second");
+        let file = dir.join(CACHE_FILE);
+        let first = Utc.ymd(2021, 11, 20).and_hms_milli(8, 0, 0, 100);
+        let second = Utc.ymd(2021, 11, 20).and_hms_milli(8, 0, 0, 900);
+        write_with_snapshot(&file, caching_data(&[], first)).unwrap();
+        write_with_snapshot(&file, caching_data(&[], second)).unwrap();
+
+        let snapshots = snapshots_in(&snapshot_dir(&file)).unwrap();
+
+        let created_at = snapshots.iter().map(|s| s.created_at).collect::<Vec<_>>();
+        assert_eq!(created_at, vec![second, first]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn snapshots_without_a_fraction_are_read() {
+        let dir = test_dir("old-snapshot");
+        fs::write(dir.join("20211120T080000Z.json"), "{}").unwrap();
+
+        let snapshots = snapshots_in(&dir).unwrap();
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(
+            snapshots[0].created_at,
+            Utc.ymd(2021, 11, 20).and_hms(8, 0, 0)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn only_the_newest_snapshots_are_kept() {
+        let dir = test_dir("max-snapshots");
+        let file = dir.join(CACHE_FILE);
+        let start = Utc.ymd(2021, 11, 20).and_hms(8, 0, 0);
+        for minute in 0..=MAX_SNAPSHOTS as i64 {
+            let created_at = start + Duration::minutes(minute);
+            write_with_snapshot(&file, caching_data(&[], created_at)).unwrap();
+        }
+
+        let snapshots = snapshots_in(&snapshot_dir(&file)).unwrap();
+
+        assert_eq!(snapshots.len(), MAX_SNAPSHOTS);
+        assert_eq!(
+            snapshots.last().unwrap().created_at,
+            start + Duration::minutes(1)
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
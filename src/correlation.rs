@@ -0,0 +1,149 @@
+use crate::data::DataPoint;
+use std::cmp::Ordering;
+use tracing::{debug, instrument};
+
+pub const DEFAULT_MAX_LAG: usize = 28;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Series {
+    Hospitalisations,
+    Deaths,
+}
+
+impl Series {
+    pub const ALL: [Series; 2] = [Series::Hospitalisations, Series::Deaths];
+
+    pub fn value(self, data_point: &DataPoint) -> f64 {
+        match self {
+            Series::Hospitalisations => f64::from(data_point.hospitalisations.increase),
+            Series::Deaths => f64::from(data_point.deaths.increase),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Series::Hospitalisations => "hospitalisations",
+            Series::Deaths => "deaths",
+        }
+    }
+}
+
+/// How well a series follows the reported cases after a number of days
+#[derive(Debug, Clone)]
+pub struct CrossCorrelation {
+    pub series: Series,
+    /// Pearson correlation coefficient for every lag from 0 to the max lag,
+    /// `None` if there is not enough variance to correlate
+    pub coefficients: Vec<Option<f64>>,
+    pub best_lag: usize,
+    pub best_coefficient: f64,
+}
+
+/// Correlates the daily reported cases with the daily increase of the
+/// hospitalisations and deaths that are shifted by up to `max_lag` data points.
+#[instrument(skip(data_points))]
+pub fn cross_correlate(data_points: &[DataPoint], max_lag: usize) -> Vec<CrossCorrelation> {
+    let cases = data_points
+        .iter()
+        .map(|d| f64::from(d.cases.reported))
+        .collect::<Vec<_>>();
+
+    Series::ALL
+        .iter()
+        .filter_map(|&series| {
+            let values = data_points
+                .iter()
+                .map(|d| series.value(d))
+                .collect::<Vec<_>>();
+            let coefficients = (0..=max_lag)
+                .map(|lag| {
+                    let len = cases.len().checked_sub(lag)?;
+                    pearson(&cases[..len], &values[lag..])
+                })
+                .collect::<Vec<_>>();
+            let (best_lag, best_coefficient) = coefficients
+                .iter()
+                .enumerate()
+                .filter_map(|(lag, c)| c.filter(|c| !c.is_nan()).map(|c| (lag, c)))
+                .max_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))?;
+            debug!(
+                "Best lag for {}: {} with {:.3}",
+                series.name(),
+                best_lag,
+                best_coefficient
+            );
+            Some(CrossCorrelation {
+                series,
+                coefficients,
+                best_lag,
+                best_coefficient,
+            })
+        })
+        .collect()
+}
+
+fn pearson(xs: &[f64], ys: &[f64]) -> Option<f64> {
+    let n = xs.len().min(ys.len());
+    if n < 2 {
+        return None;
+    }
+    let (xs, ys) = (&xs[..n], &ys[..n]);
+    let x_mean = xs.iter().sum::<f64>() / n as f64;
+    let y_mean = ys.iter().sum::<f64>() / n as f64;
+
+    let (covariance, x_variance, y_variance) = xs.iter().zip(ys).fold(
+        (0.0, 0.0, 0.0),
+        |(covariance, x_variance, y_variance), (x, y)| {
+            let (dx, dy) = (x - x_mean, y - y_mean);
+            (
+                covariance + dx * dy,
+                x_variance + dx * dx,
+                y_variance + dy * dy,
+            )
+        },
+    );
+    if x_variance == 0.0 || y_variance == 0.0 {
+        return None;
+    }
+    Some(covariance / (x_variance * y_variance).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    fn data_point(day: u32, reported: u32, deaths: i32) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.reported = reported;
+        data_point.deaths.increase = deaths;
+        data_point
+    }
+
+    #[test]
+    fn finds_the_lag_of_a_shifted_series() {
+        let reported = [0, 10, 0, 0, 20, 0, 0, 5, 0, 0, 0, 0];
+        let data = (0..reported.len())
+            .map(|i| {
+                let deaths = i.checked_sub(2).map_or(0, |j| reported[j] as i32 / 5);
+                data_point(i as u32 + 1, reported[i], deaths)
+            })
+            .collect::<Vec<_>>();
+
+        let correlations = cross_correlate(&data, 4);
+
+        // the hospitalisations never change, so they cannot be correlated
+        assert_eq!(correlations.len(), 1);
+        let deaths = &correlations[0];
+        assert_eq!(deaths.series, Series::Deaths);
+        assert_eq!(deaths.best_lag, 2);
+        assert!((deaths.best_coefficient - 1.0).abs() < 1e-9);
+        assert_eq!(deaths.coefficients.len(), 5);
+    }
+
+    #[test]
+    fn constant_series_have_no_coefficient() {
+        assert_eq!(pearson(&[1.0, 2.0, 3.0], &[4.0, 4.0, 4.0]), None);
+        assert_eq!(pearson(&[1.0], &[2.0]), None);
+    }
+}
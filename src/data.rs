@@ -13,13 +13,17 @@ pub type DataRef<'a> = &'a [DataPoint];
 pub struct CachedData {
     #[serde(with = "chrono::serde::ts_seconds")]
     pub created_at: DateTime<Utc>,
+    /// The population the incidence was calculated with, unknown for older caches
+    pub population: Option<u32>,
     pub attributes: Data,
 }
 
 #[derive(Debug, Serialize)]
 pub struct CachingData<'a> {
+    pub version: u32,
     #[serde(with = "chrono::serde::ts_seconds")]
     pub created_at: DateTime<Utc>,
+    pub population: Option<u32>,
     pub attributes: DataRef<'a>,
 }
 
@@ -340,3 +344,47 @@ mod show_indicator_format {
         Ok(indicator.is_some())
     }
 }
+
+/// Data points for the tests of the other modules
+#[cfg(test)]
+pub mod fixtures {
+    use super::*;
+
+    /// A data point on a day of November 2021, all numbers are zero
+    pub fn day(day: u32) -> DataPoint {
+        on(Utc.ymd(2021, 11, day))
+    }
+
+    /// A data point on the date, all numbers are zero
+    pub fn on(date: Date<Utc>) -> DataPoint {
+        DataPoint {
+            object_id: date.ordinal(),
+            dates: Dates {
+                date,
+                date_ts: date.and_hms(12, 0, 0),
+                date_range: String::new(),
+            },
+            show: false,
+            incidence: 0.0,
+            incidence_calculated: 0.0,
+            cases: Cases {
+                total: 0,
+                increase: 0,
+                reported: 0,
+            },
+            deaths: Deaths {
+                total: 0,
+                increase: 0,
+            },
+            recoveries: Recoveries {
+                total: 0,
+                increase: 0,
+            },
+            hospitalisations: Hospitalisations {
+                total: 0,
+                increase: 0,
+                beds_in_use: 0,
+            },
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use crate::{data::DataPoint, history::Metric};
+use chrono::{Date, Utc};
+use std::collections::HashMap;
+use tracing::instrument;
+
+/// Differences below this are rounding noise of the calculated incidence
+const TOLERANCE: f64 = 1e-6;
+
+/// A value of a day that differs between two snapshots
+#[derive(Debug, Copy, Clone)]
+pub struct Change {
+    pub date: Date<Utc>,
+    pub metric: Metric,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl Change {
+    pub fn delta(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+/// How much a metric was revised over all days
+#[derive(Debug, Copy, Clone)]
+pub struct Revision {
+    pub metric: Metric,
+    pub days: usize,
+    pub net: f64,
+    pub largest: Option<Change>,
+}
+
+#[derive(Debug)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+    /// Days that are in both snapshots
+    pub common_days: usize,
+    /// Days with at least one changed metric
+    pub revised_days: usize,
+    /// Days that are only in the newer snapshot
+    pub added_days: usize,
+    /// Days that are only in the older snapshot
+    pub removed_days: usize,
+}
+
+impl Diff {
+    pub fn revisions(&self) -> Vec<Revision> {
+        Metric::ALL
+            .iter()
+            .map(|&metric| {
+                let changes = self.changes.iter().filter(|c| c.metric == metric);
+                let mut revision = Revision {
+                    metric,
+                    days: 0,
+                    net: 0.0,
+                    largest: None,
+                };
+                for change in changes {
+                    revision.days += 1;
+                    revision.net += change.delta();
+                    if revision
+                        .largest
+                        .map_or(true, |l| change.delta().abs() > l.delta().abs())
+                    {
+                        revision.largest = Some(*change);
+                    }
+                }
+                revision
+            })
+            .collect()
+    }
+}
+
+/// Aligns both snapshots by date and collects every changed value
+#[instrument(skip(before, after))]
+pub fn diff(before: &[DataPoint], after: &[DataPoint]) -> Diff {
+    let by_date = before
+        .iter()
+        .map(|d| (d.dates.date, d))
+        .collect::<HashMap<_, _>>();
+
+    let mut changes = Vec::new();
+    let mut common_days = 0;
+    let mut revised_days = 0;
+    for new in after {
+        let old = match by_date.get(&new.dates.date) {
+            Some(old) => old,
+            None => continue,
+        };
+        common_days += 1;
+
+        let changed = changes.len();
+        changes.extend(Metric::ALL.iter().filter_map(|&metric| {
+            let before = metric.value(old);
+            let after = metric.value(new);
+            ((after - before).abs() > TOLERANCE).then_some(Change {
+                date: new.dates.date,
+                metric,
+                before,
+                after,
+            })
+        }));
+        if changes.len() > changed {
+            revised_days += 1;
+        }
+    }
+
+    Diff {
+        changes,
+        common_days,
+        revised_days,
+        added_days: after.len() - common_days,
+        removed_days: before.len() - common_days,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::Datelike;
+
+    fn data_point(day: u32, cases: i32, deaths: i32, incidence: f64) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.total = 1000;
+        data_point.cases.increase = cases;
+        data_point.deaths.total = 10;
+        data_point.deaths.increase = deaths;
+        data_point.incidence_calculated = incidence;
+        data_point
+    }
+
+    #[test]
+    fn revised_days_are_aligned_by_date() {
+        let before = [
+            data_point(1, 100, 1, 50.0),
+            data_point(2, 80, 0, 55.0),
+            data_point(3, 90, 0, 60.0),
+        ];
+        let after = [
+            data_point(2, 95, 0, 55.0),
+            data_point(3, 120, 2, 60.0 + 1e-9),
+            data_point(4, 70, 0, 62.0),
+        ];
+
+        let diff = diff(&before, &after);
+
+        assert_eq!(diff.common_days, 2);
+        assert_eq!(diff.revised_days, 2);
+        assert_eq!(diff.added_days, 1);
+        assert_eq!(diff.removed_days, 1);
+        let changes = diff
+            .changes
+            .iter()
+            .map(|c| (c.date.day(), c.metric, c.before, c.after))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changes,
+            vec![
+                (2, Metric::Cases, 80.0, 95.0),
+                (3, Metric::Cases, 90.0, 120.0),
+                (3, Metric::Deaths, 0.0, 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn revisions_sum_up_the_changes_per_metric() {
+        let before = [data_point(1, 100, 1, 50.0), data_point(2, 80, 3, 55.0)];
+        let after = [data_point(1, 90, 1, 50.0), data_point(2, 110, 2, 55.0)];
+
+        let revisions = diff(&before, &after).revisions();
+
+        let cases = revisions
+            .iter()
+            .find(|r| r.metric == Metric::Cases)
+            .unwrap();
+        assert_eq!(cases.days, 2);
+        assert_eq!(cases.net, 20.0);
+        assert_eq!(cases.largest.map(|l| l.delta()), Some(30.0));
+        let incidence = revisions
+            .iter()
+            .find(|r| r.metric == Metric::Incidence)
+            .unwrap();
+        assert_eq!(incidence.days, 0);
+        assert!(incidence.largest.is_none());
+    }
+}
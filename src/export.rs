@@ -0,0 +1,161 @@
+use crate::{
+    data::DataPoint,
+    output::{self, Record},
+    rates, Result,
+};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use color_eyre::{eyre::eyre, Help};
+use std::io::Write;
+use tracing::{debug, instrument};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    Csv,
+    Json,
+    /// Only available if covidd was built with the `parquet` feature
+    Parquet,
+}
+
+/// The time series between `since` and `until`, both inclusive, with only the
+/// selected metrics, or all of them if none are selected.
+///
+/// Rates are calculated before the range is applied, so that the first
+/// exported points can use the data before them.
+#[instrument(skip(data_points))]
+pub fn records(
+    data_points: &[DataPoint],
+    rates: rates::Config,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    metrics: &[String],
+) -> Result<Vec<Record>> {
+    let series = output::series(data_points, rates);
+
+    if let Some(first) = series.first() {
+        let available = first.keys().filter(|k| *k != "date").collect::<Vec<_>>();
+        if let Some(unknown) = metrics.iter().find(|m| !available.contains(&m.as_str())) {
+            return Err(eyre!("Unknown metric: {}", unknown)
+                .suggestion(format!("Use one of {}.", available.join(", "))));
+        }
+    }
+
+    let records = data_points
+        .iter()
+        .zip(series)
+        .filter(|(d, _)| {
+            let date = d.dates.date.naive_utc();
+            since.map_or(true, |since| date >= since) && until.map_or(true, |until| date <= until)
+        })
+        .map(|(_, record)| {
+            if metrics.is_empty() {
+                record
+            } else {
+                record.select(std::iter::once("date").chain(metrics.iter().map(String::as_str)))
+            }
+        })
+        .collect::<Vec<_>>();
+    debug!("Exporting {} data points", records.len());
+
+    Ok(records)
+}
+
+pub fn write(out: impl Write, format: Format, records: &[Record]) -> Result<()> {
+    match format {
+        Format::Csv => output::write_all(out, output::Format::Csv, records),
+        Format::Json => output::write_all(out, output::Format::Json, records),
+        Format::Parquet => write_parquet(out, records),
+    }
+}
+
+#[cfg(feature = "parquet")]
+fn write_parquet(out: impl Write, records: &[Record]) -> Result<()> {
+    crate::parquet::write(out, records)
+}
+
+#[cfg(not(feature = "parquet"))]
+fn write_parquet(_out: impl Write, _records: &[Record]) -> Result<()> {
+    Err(eyre!("covidd was built without Parquet support")
+        .suggestion("Install covidd with `--features parquet` or export to CSV or JSON."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use serde_json::{json, Value};
+
+    fn data() -> Vec<DataPoint> {
+        (1..=10)
+            .map(|day| {
+                let mut data_point = fixtures::day(day);
+                data_point.cases.increase = 10;
+                data_point.deaths.increase = 1;
+                data_point
+            })
+            .collect()
+    }
+
+    const RATES: rates::Config = rates::Config { window: 2, lag: 1 };
+
+    fn date(day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(2021, 11, day)
+    }
+
+    fn dates(records: &[Record]) -> Vec<&Value> {
+        records.iter().map(|r| r.get("date").unwrap()).collect()
+    }
+
+    #[test]
+    fn window_includes_both_ends() {
+        let records = records(&data(), RATES, date(3), date(5), &[]).unwrap();
+
+        assert_eq!(
+            dates(&records),
+            vec![
+                &json!("2021-11-03"),
+                &json!("2021-11-04"),
+                &json!("2021-11-05")
+            ]
+        );
+    }
+
+    #[test]
+    fn open_window_exports_everything() {
+        let records = records(&data(), RATES, None, None, &[]).unwrap();
+
+        assert_eq!(records.len(), 10);
+    }
+
+    #[test]
+    fn only_the_selected_metrics_in_their_order() {
+        let metrics = [
+            String::from("deaths_increase"),
+            String::from("cases_increase"),
+        ];
+
+        let records = records(&data(), RATES, None, date(1), &metrics).unwrap();
+
+        let keys = records[0].keys().collect::<Vec<_>>();
+        assert_eq!(keys, vec!["date", "deaths_increase", "cases_increase"]);
+        assert_eq!(records[0].get("cases_increase"), Some(&json!(10)));
+    }
+
+    #[test]
+    fn unknown_metric_is_an_error() {
+        let metrics = [String::from("cases"), String::from("vaccinations")];
+
+        let error = records(&data(), RATES, None, None, &metrics).unwrap_err();
+
+        assert_eq!(error.to_string(), "Unknown metric: vaccinations");
+    }
+
+    #[test]
+    fn rates_use_the_data_before_the_window() {
+        let metrics = [String::from("case_fatality_rate")];
+
+        let records = records(&data(), RATES, date(3), None, &metrics).unwrap();
+
+        assert_eq!(records[0].get("case_fatality_rate"), Some(&json!(10.0)));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::data::DataPoint;
+use chrono::{Date, Utc};
+use tracing::instrument;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Metric {
+    Incidence,
+    Cases,
+    Deaths,
+    Hospitalisations,
+    Recoveries,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 5] = [
+        Metric::Incidence,
+        Metric::Cases,
+        Metric::Deaths,
+        Metric::Hospitalisations,
+        Metric::Recoveries,
+    ];
+
+    /// The daily value of the metric, totals always grow and are not interesting here
+    pub fn value(self, data_point: &DataPoint) -> f64 {
+        match self {
+            Metric::Incidence => data_point.incidence_calculated,
+            Metric::Cases => f64::from(data_point.cases.increase),
+            Metric::Deaths => f64::from(data_point.deaths.increase),
+            Metric::Hospitalisations => f64::from(data_point.hospitalisations.increase),
+            Metric::Recoveries => f64::from(data_point.recoveries.increase),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::Incidence => "incidence",
+            Metric::Cases => "cases_increase",
+            Metric::Deaths => "deaths_increase",
+            Metric::Hospitalisations => "hospitalisations_increase",
+            Metric::Recoveries => "recoveries_increase",
+        }
+    }
+}
+
+/// How the latest value of a metric compares to all previous values
+#[derive(Debug, Copy, Clone)]
+pub struct Context {
+    pub metric: Metric,
+    pub value: f64,
+    /// Percentage of all values that are lower than or equal to the latest value
+    pub percentile: f64,
+    pub max: f64,
+    pub max_date: Date<Utc>,
+    /// The last date with a higher value, `None` if the latest value is an all-time high
+    pub highest_since: Option<Date<Utc>>,
+    /// The last date with a lower value, `None` if the latest value is an all-time low
+    pub lowest_since: Option<Date<Utc>>,
+}
+
+impl Context {
+    /// The more remarkable one of the two "since" dates, the one further in the past
+    pub fn notable(&self) -> Notable {
+        match (self.highest_since, self.lowest_since) {
+            (None, _) => Notable::AllTimeHigh,
+            (_, None) => Notable::AllTimeLow,
+            (Some(higher), Some(lower)) if higher <= lower => Notable::HighestSince(higher),
+            (_, Some(lower)) => Notable::LowestSince(lower),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Notable {
+    AllTimeHigh,
+    AllTimeLow,
+    HighestSince(Date<Utc>),
+    LowestSince(Date<Utc>),
+}
+
+#[instrument(skip(data_points))]
+pub fn contexts(data_points: &[DataPoint]) -> Vec<Context> {
+    Metric::ALL
+        .iter()
+        .filter_map(|metric| context(data_points, *metric))
+        .collect()
+}
+
+pub fn context(data_points: &[DataPoint], metric: Metric) -> Option<Context> {
+    let (latest, previous) = data_points.split_last()?;
+    let value = metric.value(latest);
+
+    let not_higher = data_points
+        .iter()
+        .filter(|d| metric.value(d) <= value)
+        .count();
+    let percentile = not_higher as f64 * 100.0 / data_points.len() as f64;
+
+    let (max, max_date) = data_points
+        .iter()
+        .map(|d| (metric.value(d), d.dates.date))
+        .fold(None, |max: Option<(f64, Date<Utc>)>, (v, date)| match max {
+            Some((m, _)) if m >= v => max,
+            _ => Some((v, date)),
+        })?;
+
+    let highest_since = previous
+        .iter()
+        .rev()
+        .find(|d| metric.value(d) > value)
+        .map(|d| d.dates.date);
+    let lowest_since = previous
+        .iter()
+        .rev()
+        .find(|d| metric.value(d) < value)
+        .map(|d| d.dates.date);
+
+    Some(Context {
+        metric,
+        value,
+        percentile,
+        max,
+        max_date,
+        highest_since,
+        lowest_since,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::TimeZone;
+
+    /// Daily data from 1 November 2021 with the given new cases
+    fn cases(increases: &[i32]) -> Vec<DataPoint> {
+        increases
+            .iter()
+            .enumerate()
+            .map(|(i, &increase)| {
+                let mut data_point = fixtures::day(i as u32 + 1);
+                data_point.cases.increase = increase;
+                data_point
+            })
+            .collect()
+    }
+
+    fn date(day: u32) -> Date<Utc> {
+        Utc.ymd(2021, 11, day)
+    }
+
+    #[test]
+    fn percentile_counts_equal_values() {
+        let context = context(&cases(&[10, 30, 20, 40, 20]), Metric::Cases).unwrap();
+
+        assert_eq!(context.value, 20.0);
+        assert_eq!(context.percentile, 60.0);
+        assert_eq!(context.max, 40.0);
+        assert_eq!(context.max_date, date(4));
+    }
+
+    #[test]
+    fn highest_since_the_last_higher_value() {
+        let context = context(&cases(&[50, 10, 30, 20, 40]), Metric::Cases).unwrap();
+
+        assert_eq!(context.highest_since, Some(date(1)));
+        assert_eq!(context.lowest_since, Some(date(4)));
+        assert_eq!(context.notable(), Notable::HighestSince(date(1)));
+    }
+
+    #[test]
+    fn lowest_since_the_last_lower_value() {
+        let context = context(&cases(&[5, 30, 20, 40, 10]), Metric::Cases).unwrap();
+
+        assert_eq!(context.highest_since, Some(date(4)));
+        assert_eq!(context.lowest_since, Some(date(1)));
+        assert_eq!(context.notable(), Notable::LowestSince(date(1)));
+    }
+
+    #[test]
+    fn equal_values_are_not_higher_or_lower() {
+        let context = context(&cases(&[40, 10, 40, 20, 40]), Metric::Cases).unwrap();
+
+        assert_eq!(context.highest_since, None);
+        assert_eq!(context.lowest_since, Some(date(4)));
+        assert_eq!(context.notable(), Notable::AllTimeHigh);
+        // the first day with the maximum
+        assert_eq!(context.max_date, date(1));
+    }
+
+    #[test]
+    fn all_time_low() {
+        let context = context(&cases(&[30, 10, 20, 0]), Metric::Cases).unwrap();
+
+        assert_eq!(context.lowest_since, None);
+        assert_eq!(context.notable(), Notable::AllTimeLow);
+        assert_eq!(context.percentile, 25.0);
+    }
+
+    #[test]
+    fn single_data_point() {
+        let context = context(&cases(&[10]), Metric::Cases).unwrap();
+
+        assert_eq!(context.percentile, 100.0);
+        assert_eq!(context.max_date, date(1));
+        assert_eq!(context.highest_since, None);
+        assert_eq!(context.lowest_since, None);
+        assert_eq!(context.notable(), Notable::AllTimeHigh);
+    }
+
+    #[test]
+    fn no_context_without_data() {
+        assert!(context(&[], Metric::Cases).is_none());
+        assert!(contexts(&[]).is_empty());
+    }
+}
@@ -8,6 +8,7 @@ Render COVID-19 case data for Dresden in the terminal
 
 This tool is build with Rust so you need to have a rust toolchain and cargo installed.
 If you don't, please visit [https://rustup.rs/](https://rustup.rs/) and follow their instructions.
+covidd needs Rust 1.70 or newer.
 
 ## Building
 
@@ -20,6 +21,12 @@ make install
 If you do not have a fairly recent `make` (on macOS, homebrew can install a newer version),
 or don't want to use make, you can also run `cargo install --path .`.
 
+To export the data as Parquet with `covidd export --format parquet`, add `--features parquet` to the `cargo install` command.
+To keep every download in an SQLite database and query it with `covidd sql`, add `--features sqlite`.
+To store the cache in a compact binary encoding that starts much faster with years of data, add `--features binary-cache`.
+The encoding is chosen when covidd is built: a build with the feature writes every cache in the binary encoding and still reads JSON caches, a build without it cannot read binary caches.
+`make bench` compares how long `covidd export` takes to start with five years of data in either encoding.
+
 ## Already built binaries
 
 If you don't want to compile on your own, you can find binaries at [the Github release page](https://github.com/knutwalker/covidd/releases).
@@ -32,101 +39,671 @@ Run `covidd`.
 - Press Down/Left to zoom out
 - Press Home/End to fully zoom in/out
 - Press 1 through 9 to zoom to the latest <n> weeks
+- Press t to toggle the trend of daily cases, without the weekday effect
+- Press r to toggle the case fatality and hospitalisation rates
+- Press w or W to toggle the comparison of all waves, aligned on their start or peak
+- Press y to toggle the comparison of the incidence by calendar year
+- Press c to toggle the new cases, hospitalisations and deaths, shifted by how many days they trail each other
+- Press i to toggle a panel that puts the latest numbers into historical context
 - Press q to quit
 
 Run `covidd --help` for an overview of more available options.
 
+### Cache location
+
+Downloads are cached in the cache directory of your user.
+Once the cache is older than `--stale-after`, the charts show the cached data right away and switch to the new data when its download finished.
+Use `--cache-dir <DIR>` or the `COVIDD_CACHE_DIR` environment variable to keep the cache somewhere else,
+`--portable` to keep it in a `covidd-cache` directory next to the binary, or `--no-cache` to always download.
+To look at the exact same data on another machine, run `covidd cache export -o <FILE>` and `covidd cache import <FILE>` on the other machine,
+then run covidd there with `--cache` so that the imported data is not replaced by a download.
+`covidd cache export --json` writes the cached data as plain JSON instead, also when it is stored in the binary encoding.
+
 ### Screenshot
 
 ![have a look at doc/screenshot.png](https://knutwalker.s3.eu-central-1.amazonaws.com/covidd/doc/screenshot.png)
 
 */
-use std::fmt::Display;
-
-use args::{CacheCommand, Command, Run};
-use chrono::{DateTime, Duration, Utc};
+use args::{CacheCommand, Command, Diff, Export, Forecast, Lags, Run, Source, Sql, Waves};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
 use color_eyre::{eyre::eyre, Help, Result};
 use data::{CachedData, Data, DataPoint};
+use once_cell::sync::Lazy;
+use output::Format;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex, MutexGuard, PoisonError},
+    thread,
+};
+use summary::OptionalValue;
 use tracing::{debug, instrument, trace, warn};
 
 mod api;
 mod args;
+#[cfg(feature = "binary-cache")]
+mod binary;
+mod bundle;
 mod cache;
+mod correlation;
 mod data;
+mod diff;
+mod export;
+mod history;
 mod messages;
+mod migrations;
+mod nowcast;
+mod output;
+#[cfg(feature = "parquet")]
+mod parquet;
+mod projection;
+mod rates;
+mod resample;
+mod seasonal;
+#[cfg(feature = "sqlite")]
+mod store;
+mod summary;
 mod ui;
+mod verify;
+mod waves;
 
 #[instrument]
 fn main() -> Result<()> {
-    let cmd = Command::get();
+    let (cmd, location) = Command::get();
 
     install_tracing(cmd.verbosity());
     install_eyre()?;
+    cache::set_location(location.location()?);
 
-    let data_for_ui = match cmd {
+    match cmd {
         Command::Cache(c) => cache_command(c.cmd)?,
+        Command::Forecast(f) => forecast_command(f)?,
+        Command::Waves(w) => waves_command(w)?,
+        Command::Lags(l) => lags_command(l)?,
+        Command::Export(e) => export_command(e)?,
+        Command::Sql(s) => sql_command(s)?,
+        Command::Diff(d) => diff_command(d)?,
         Command::Run(r) => run_command(r)?,
     };
 
-    if let Some(data) = data_for_ui {
-        if atty::is(atty::Stream::Stdout) {
-            let msg = messages::Messages::user_default();
-            ui::draw(&data, msg)?;
+    Ok(())
+}
+
+#[instrument(err)]
+fn run_command(r: Run) -> Result<()> {
+    let mut options = ui::Options {
+        projection: r.projection(),
+        rates: r.rates.config(),
+        wave_prominence: r.wave_prominence,
+        ..ui::Options::default()
+    };
+    let shows_ui =
+        r.format == Format::Text && !r.series && !r.no_ui && atty::is(atty::Stream::Stdout);
+    let nowcast = r.nowcast && r.resolution == resample::Resolution::Daily;
+    let data = match r.as_of {
+        Some(date) => {
+            let data = data_as_of(date)?;
+            options.as_of = Some((date, data.created_at));
+            data.attributes
+        }
+        None if shows_ui => {
+            let (data, refresh) = data_with_background_refresh(r.source, r.resolution, nowcast)?;
+            options.refresh = refresh;
+            data
+        }
+        None => current_data_with_updated_cache(r.source)?,
+    };
+    if r.nowcast {
+        if nowcast {
+            options.nowcast = nowcast_of(&data, options.as_of.map(|(date, _)| date))?;
         } else {
-            let data = summarized_data(&data);
-            println!("{}", data)
+            warn!("The nowcast is only available for daily data");
+        }
+    }
+    let data = resample::resample(&data, r.resolution);
+    if r.no_ui {
+        return Ok(());
+    }
+
+    if shows_ui {
+        let msg = messages::Messages::user_default();
+        show_charts(data, msg, options)?;
+    } else if r.series {
+        let mut series = output::series(&data, options.rates);
+        if r.nowcast {
+            nowcast::add_to_series(&mut series, &options.nowcast);
         }
+        ignore_broken_pipe(output::write_all(
+            std::io::stdout().lock(),
+            r.format,
+            &series,
+        ))?;
+    } else {
+        let compare = r
+            .compare
+            .map(|c| Duration::from_std(c.into()))
+            .transpose()?;
+        let mut data = summary::summarized_data(&data, compare, options.rates);
+        data.incidence_nowcast = options.nowcast.last().map(|p| p.incidence);
+        let mut record = data.record();
+        if let Some((_, fetched_at)) = options.as_of {
+            record.push("as_of", fetched_at.to_rfc3339());
+        }
+        ignore_broken_pipe(output::write_one(
+            std::io::stdout().lock(),
+            r.format,
+            &record,
+        ))?;
+    }
+
+    Ok(())
+}
+
+#[instrument(err)]
+fn forecast_command(f: Forecast) -> Result<()> {
+    let config = f.projection();
+    let data = current_data_with_updated_cache(f.source)?;
+    let data = resample::resample(&data, f.resolution);
+    let projection = projection::project(&data, config).ok_or_else(|| {
+        eyre!("Not enough data to fit a projection")
+            .suggestion("Use a larger --window or a finer --resolution.")
+    })?;
+    ignore_broken_pipe(write_projection(std::io::stdout().lock(), &projection))
+}
+
+fn write_projection(mut out: impl Write, projection: &projection::Projection) -> Result<()> {
+    writeln!(out, "date\tincidence\tincidence_lower\tincidence_upper\tnew_cases\tnew_cases_lower\tnew_cases_upper\tcases\tcases_lower\tcases_upper")?;
+    for p in &projection.points {
+        writeln!(
+            out,
+            "{}\t{:.1}\t{:.1}\t{:.1}\t{:.0}\t{:.0}\t{:.0}\t{:.0}\t{:.0}\t{:.0}",
+            p.date.format("%Y-%m-%d"),
+            p.incidence.value,
+            p.incidence.lower,
+            p.incidence.upper,
+            p.new_cases.value,
+            p.new_cases.lower,
+            p.new_cases.upper,
+            p.total_cases.value,
+            p.total_cases.lower,
+            p.total_cases.upper,
+        )?;
     }
 
     Ok(())
 }
 
 #[instrument(err)]
-fn run_command(r: Run) -> Result<Option<Data>> {
-    let show_ui = !r.no_ui;
-    let data = current_data_with_updated_cache(r)?;
-    if show_ui {
-        Ok(Some(data))
+fn waves_command(w: Waves) -> Result<()> {
+    let data = current_data_with_updated_cache(w.source)?;
+    let waves = waves::detect(&data, w.prominence);
+    ignore_broken_pipe(write_waves(std::io::stdout().lock(), &data, &waves))
+}
+
+fn write_waves(mut out: impl Write, data: &[DataPoint], waves: &[waves::Wave]) -> Result<()> {
+    writeln!(out, "wave\tstart\tpeak\tpeak_incidence\tend\tdays\tcases")?;
+    for (number, wave) in waves.iter().enumerate() {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{:.1}\t{}\t{}\t{}",
+            number + 1,
+            wave.start_date(data).format("%Y-%m-%d"),
+            wave.peak_date(data).format("%Y-%m-%d"),
+            wave.peak_incidence(data),
+            wave.end_date(data).format("%Y-%m-%d"),
+            wave.days(data),
+            wave.cases(data),
+        )?;
+    }
+
+    Ok(())
+}
+
+#[instrument(err)]
+fn lags_command(l: Lags) -> Result<()> {
+    let data = current_data_with_updated_cache(l.source)?;
+
+    if l.plot {
+        if atty::is(atty::Stream::Stdout) {
+            let msg = messages::Messages::user_default();
+            let options = ui::Options {
+                max_lag: l.max_lag,
+                show_lags: true,
+                ..ui::Options::default()
+            };
+            return show_charts(data, msg, options);
+        }
+        warn!("Cannot plot the lags when the output is not a terminal, printing the best lags instead");
+    }
+
+    let correlations = correlation::cross_correlate(&data, l.max_lag);
+    ignore_broken_pipe(write_lags(std::io::stdout().lock(), &correlations, l.all))
+}
+
+fn write_lags(
+    mut out: impl Write,
+    correlations: &[correlation::CrossCorrelation],
+    all: bool,
+) -> Result<()> {
+    if all {
+        writeln!(out, "series\tlag\tcoefficient")?;
+        for correlation in correlations {
+            for (lag, coefficient) in correlation.coefficients.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}",
+                    correlation.series.name(),
+                    lag,
+                    OptionalValue(coefficient.map(|c| format!("{:.3}", c))),
+                )?;
+            }
+        }
     } else {
-        Ok(None)
+        writeln!(out, "series\tbest_lag\tcoefficient")?;
+        for correlation in correlations {
+            writeln!(
+                out,
+                "{}\t{}\t{:.3}",
+                correlation.series.name(),
+                correlation.best_lag,
+                correlation.best_coefficient,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+#[instrument(err)]
+fn export_command(e: Export) -> Result<()> {
+    let data = current_data_with_updated_cache(e.source)?;
+    let data = resample::resample(&data, e.resolution);
+    let records = export::records(&data, e.rates.config(), e.since, e.until, &e.metrics)?;
+
+    match e.output {
+        Some(file) => {
+            let out = BufWriter::new(File::create(file)?);
+            export::write(out, e.format, &records)?;
+        }
+        None => {
+            if e.format == export::Format::Parquet && atty::is(atty::Stream::Stdout) {
+                return Err(eyre!("Refusing to write Parquet to a terminal")
+                    .suggestion("Use --output to write to a file or redirect stdout."));
+            }
+            ignore_broken_pipe(export::write(std::io::stdout().lock(), e.format, &records))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+#[instrument(err)]
+fn sql_command(s: Sql) -> Result<()> {
+    let records = store::query(&s.query)?;
+    ignore_broken_pipe(output::write_all(
+        std::io::stdout().lock(),
+        s.format,
+        &records,
+    ))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn sql_command(_: Sql) -> Result<()> {
+    Err(eyre!("covidd was built without SQLite support")
+        .suggestion("Install covidd with `--features sqlite`."))
+}
+
+#[instrument(err)]
+fn diff_command(d: Diff) -> Result<()> {
+    let (before, after) = match (d.before, d.after) {
+        (Some(before), Some(after)) => (snapshot(&before)?, snapshot(&after)?),
+        (before, _) => {
+            let mut snapshots = cache::snapshots()?.into_iter();
+            let newest = snapshots.next();
+            let before = match before {
+                Some(before) => Some(snapshot(&before)?),
+                None => snapshots.next(),
+            };
+            match (before, newest) {
+                (Some(before), Some(after)) => (before, after),
+                _ => {
+                    return Err(eyre!("There are not enough snapshots to compare").suggestion(
+                        "Snapshots are kept on every download, run the `cache refresh` subcommand to take one.",
+                    ))
+                }
+            }
+        }
+    };
+    debug!(
+        "Comparing the snapshot from {} to the one from {}",
+        before.created_at, after.created_at
+    );
+
+    let diff = diff::diff(
+        &cache::read_snapshot(&before)?.attributes,
+        &cache::read_snapshot(&after)?.attributes,
+    );
+
+    ignore_broken_pipe(write_diff(std::io::stdout().lock(), &diff))
+}
+
+fn write_diff(mut out: impl Write, diff: &diff::Diff) -> Result<()> {
+    writeln!(out, "date\tmetric\tbefore\tafter\tchange")?;
+    for change in &diff.changes {
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t{:+}",
+            summary::format_date(change.date),
+            change.metric.name(),
+            change.before,
+            change.after,
+            change.delta(),
+        )?;
+    }
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{} of {} days revised, {} days added, {} days removed",
+        diff.revised_days, diff.common_days, diff.added_days, diff.removed_days
+    )?;
+    for revision in diff.revisions() {
+        if let Some(largest) = revision.largest {
+            writeln!(
+                out,
+                "{:<26} {:>4} days  net {:>+8.1}  largest {:>+8.1} on {}",
+                revision.metric.name(),
+                revision.days,
+                revision.net,
+                largest.delta(),
+                summary::format_date(largest.date),
+            )?;
+        }
     }
+
+    Ok(())
+}
+
+fn snapshot(name: &str) -> Result<cache::Snapshot> {
+    cache::find_snapshot(name)?.ok_or_else(|| {
+        eyre!("There is no snapshot {}", name)
+            .suggestion("Run the `cache list` subcommand to see the available snapshots.")
+    })
 }
 
 #[instrument(err)]
-fn cache_command(c: CacheCommand) -> Result<Option<Data>> {
+fn cache_command(c: CacheCommand) -> Result<()> {
     match c {
         CacheCommand::List => {
             if let Some((file, data)) = cache::get_cached()? {
                 println!("{}\t{}", file.display(), data.created_at);
             }
+            for snapshot in cache::snapshots()? {
+                println!("{}\t{}", snapshot.file.display(), snapshot.created_at);
+            }
+        }
+        CacheCommand::Prune(p) => {
+            let older_than = p
+                .older_than
+                .map(|d| Duration::from_std(d.into()))
+                .transpose()?;
+            for snapshot in cache::prune_snapshots(p.keep, older_than)? {
+                println!("{}\t{}", snapshot.file.display(), snapshot.created_at);
+            }
+            #[cfg(feature = "sqlite")]
+            store::prune(p.keep, older_than)?;
         }
         CacheCommand::Flush => cache::remove_cache()?,
+        CacheCommand::Export(e) => {
+            let json = e.json;
+            let export = |out: &mut dyn Write| {
+                if json {
+                    cache::export_json(out)
+                } else {
+                    bundle::export(out)
+                }
+            };
+            match e.output {
+                Some(file) => export(&mut BufWriter::new(File::create(file)?))?,
+                None => ignore_broken_pipe(export(&mut std::io::stdout().lock()))?,
+            }
+        }
+        CacheCommand::Info => cache_info()?,
+        CacheCommand::Verify => cache_verify()?,
+        CacheCommand::Import(i) => {
+            let data = bundle::import(BufReader::new(File::open(i.file)?))?;
+            let file = cache::store_imported(&data)?;
+            #[cfg(feature = "sqlite")]
+            if let Some(population) = data.population {
+                store::record_snapshot(&data.attributes, population, data.created_at);
+            }
+            println!(
+                "Imported {} data points fetched at {} into {}",
+                data.attributes.len(),
+                data.created_at,
+                file.display()
+            );
+        }
         CacheCommand::Refresh => {
-            let _ = current_data_with_updated_cache(Run {
+            let _ = current_data_with_updated_cache(Source {
                 force: true,
-                ..Run::default()
+                ..Source::default()
             })?;
         }
     };
-    Ok(None)
+    Ok(())
+}
+
+fn existing_cache_file() -> Result<PathBuf> {
+    cache::cache_file()
+        .filter(|file| file.exists())
+        .ok_or_else(|| {
+            eyre!("There is no cached data")
+                .suggestion("Run the `cache refresh` subcommand to download the data.")
+        })
+}
+
+fn cache_info() -> Result<()> {
+    let file = existing_cache_file()?;
+    ignore_broken_pipe(write_cache_info(std::io::stdout().lock(), &file))
+}
+
+fn write_cache_info(mut out: impl Write, file: &Path) -> Result<()> {
+    let size = file.metadata()?.len();
+    let encoding = if cache::is_binary(file)? {
+        "binary"
+    } else {
+        "json"
+    };
+    let data = cache::read_cache_file(file)?;
+    let age = (Utc::now() - data.created_at).num_seconds().max(0) as u64;
+
+    writeln!(out, "file: {}", file.display())?;
+    writeln!(out, "encoding: {}", encoding)?;
+    writeln!(out, "size: {} bytes", size)?;
+    writeln!(out, "fetched_at: {}", data.created_at)?;
+    writeln!(
+        out,
+        "age: {}",
+        humantime::format_duration(std::time::Duration::from_secs(age))
+    )?;
+    writeln!(out, "data_points: {}", data.attributes.len())?;
+    if let (Some(first), Some(last)) = (data.attributes.first(), data.attributes.last()) {
+        writeln!(
+            out,
+            "first_date: {}",
+            summary::format_date(first.dates.date)
+        )?;
+        writeln!(out, "last_date: {}", summary::format_date(last.dates.date))?;
+    }
+    writeln!(out, "population: {}", OptionalValue(data.population))?;
+    writeln!(out, "snapshots: {}", cache::snapshots()?.len())?;
+    // the cache does not record where its data came from
+    for source in api::sources().iter() {
+        writeln!(out, "configured_source: {}", source)?;
+    }
+    Ok(())
 }
 
-fn current_data_with_updated_cache(r: Run) -> Result<Data> {
+fn cache_verify() -> Result<()> {
+    let file = existing_cache_file()?;
+    let checks = verify::verify(&file);
+    for check in &checks {
+        println!("{}: {}", check.name, check.outcome);
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|c| matches!(c.outcome, verify::Outcome::Failed(_)))
+        .count();
+    if failed > 0 {
+        return Err(
+            eyre!("The cache failed {} of {} checks", failed, checks.len()).suggestion(
+                "Run the `cache refresh` subcommand to replace the cache with new data.",
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Stops quietly if stdout is closed early, e.g. when piped into `head`
+fn ignore_broken_pipe(result: Result<()>) -> Result<()> {
+    match result {
+        Err(e)
+            if e.chain().any(|e| {
+                e.downcast_ref::<std::io::Error>()
+                    .is_some_and(|e| e.kind() == std::io::ErrorKind::BrokenPipe)
+            }) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+fn current_data_with_updated_cache(r: Source) -> Result<Data> {
     let cached_data = cached_data_if_current(r.force, r.cache, r.stale_after)?;
 
     let data = if let Some(data) = cached_data {
         debug!("Using data from cache from {}", data.created_at);
         data.attributes
     } else {
-        debug!("Calling API for new data");
-        let data = api::call(r.timeout.into())?;
-        cache::store_data(&data)?;
-        data
+        download_single_flight(&r)?
+    };
+
+    Ok(data)
+}
+
+/// Stale cached data is returned right away while new data is downloaded in the background
+fn data_with_background_refresh(
+    r: Source,
+    resolution: resample::Resolution,
+    nowcast: bool,
+) -> Result<(Data, Option<ui::Refresh>)> {
+    if r.force || r.cache {
+        return Ok((current_data_with_updated_cache(r)?, None));
+    }
+    let cached = match cache::get_cached_data()? {
+        Some(cached) => cached,
+        None => return Ok((download_single_flight(&r)?, None)),
     };
+    if !cache_is_stale(cached.created_at, r.stale_after)? {
+        debug!("Using data from cache from {}", cached.created_at);
+        return Ok((cached.attributes, None));
+    }
 
+    debug!(
+        "Showing data from cache from {} while downloading new data",
+        cached.created_at
+    );
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let refreshed = download_single_flight(&r).and_then(|data| {
+            let nowcast = if nowcast {
+                nowcast_of(&data, None)?
+            } else {
+                Vec::new()
+            };
+            Ok(ui::Refreshed {
+                data_points: resample::resample(&data, resolution),
+                nowcast,
+            })
+        });
+        // the UI is gone if it was closed before the download finished
+        let _ = sender.send(refreshed);
+    });
+    let refresh = ui::Refresh {
+        fetched_at: cached.created_at,
+        receiver,
+    };
+    Ok((cached.attributes, Some(refresh)))
+}
+
+/// Downloads new data, unless another process is already doing that, then its data is used
+fn download_single_flight(r: &Source) -> Result<Data> {
+    let requested = Utc::now();
+    let lock = cache::lock_download(r.lock_timeout.into());
+    // another process might have finished its download since the cache was checked
+    let downloaded = match cache::get_cached_data()? {
+        Some(data) if data.created_at.timestamp() >= requested.timestamp() => Some(data),
+        Some(data) if !r.force && !cache_is_stale(data.created_at, r.stale_after)? => Some(data),
+        _ => None,
+    };
+    if let Some(data) = downloaded {
+        debug!(
+            "Using data from another process from {}, waited for it: {}",
+            data.created_at, lock.waited
+        );
+        return Ok(data.attributes);
+    }
+
+    debug!("Calling API for new data");
+    let (data, population) = api::call(r.timeout.into())?;
+    let fetched_at = Utc::now();
+    cache::store_data(&data, population, fetched_at)?;
+    #[cfg(feature = "sqlite")]
+    store::record_snapshot(&data, population, fetched_at);
+
+    drop(lock);
     Ok(data)
 }
 
+/// Nowcast from all snapshots that were fetched until the given date
+fn nowcast_of(data: &[DataPoint], until: Option<NaiveDate>) -> Result<Vec<nowcast::NowcastPoint>> {
+    let mut snapshots = cache::snapshots()?;
+    if let Some(until) = until {
+        snapshots.retain(|s| s.created_at.date().naive_utc() <= until);
+    }
+    let snapshots = snapshots
+        .iter()
+        .rev()
+        .filter_map(|s| match cache::read_snapshot(s) {
+            Ok(data) => Some(data.attributes),
+            Err(e) => {
+                warn!("Could not read the snapshot at [{}], it is left out of the nowcast. Error: [{}].", s.file.display(), e);
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+
+    match nowcast::delays(&snapshots, nowcast::DEFAULT_MAX_DELAY) {
+        Some(delays) => Ok(nowcast::nowcast(data, &delays)),
+        None => {
+            warn!("There are not enough snapshots to estimate the reporting delay, the nowcast needs snapshots from several days that are at least {} days apart from the newest one.", nowcast::DEFAULT_MAX_DELAY);
+            Ok(Vec::new())
+        }
+    }
+}
+
+fn data_as_of(date: NaiveDate) -> Result<CachedData> {
+    cache::get_cached_as_of(date)?.ok_or_else(|| {
+        eyre!("There is no data that was fetched on or before {}", date)
+            .suggestion("Run the `cache list` subcommand to see the available snapshots.")
+    })
+}
+
 fn cached_data_if_current(
     ignore_cache: bool,
     force_cache: bool,
@@ -166,7 +743,8 @@ fn data_from_cache(force: bool) -> Result<Option<CachedData>> {
 fn cache_is_stale(created: DateTime<Utc>, stale_after: humantime::Duration) -> Result<bool> {
     let stale_after = Duration::from_std(stale_after.into())?;
     let now = Utc::now();
-    let age = now - created;
+    // data fetched on a machine whose clock is ahead is as fresh as it gets
+    let age = (now - created).max(Duration::zero());
     let is_current = age < stale_after;
     trace!(
         "Cached data: created={}, age={}, current={}",
@@ -177,58 +755,66 @@ fn cache_is_stale(created: DateTime<Utc>, stale_after: humantime::Duration) -> R
     Ok(!is_current)
 }
 
-fn summarized_data(data_points: &[DataPoint]) -> SummarizedData {
-    let mut data = SummarizedData::default();
-    let mut dp = data_points.iter().rev();
+/// The logs are held back while the charts are shown, as they would be written over the charts
+fn show_charts(data: Data, msg: messages::Messages, options: ui::Options) -> Result<()> {
+    let _held = HeldLogs::hold();
+    ui::draw(data, msg, options)
+}
+
+/// Logs that were written while the charts were shown, `None` when the logs are not held back
+static HELD_LOGS: Lazy<Mutex<Option<Vec<u8>>>> = Lazy::new(|| Mutex::new(None));
+
+/// The charts can be shown for days, logs beyond this size are left out
+const MAX_HELD_LOGS: usize = 1024 * 1024;
+
+/// Holds back the logs until it is dropped, then they are written to stderr
+struct HeldLogs;
 
-    if let Some(dp) = dp.next() {
-        data.incidence = dp.incidence_calculated;
-        data.cases = dp.cases.total;
-        data.deaths = dp.deaths.total;
-        data.hospitalisations = dp.hospitalisations.total;
-        data.recoveries = dp.recoveries.total;
+impl HeldLogs {
+    fn hold() -> Self {
+        *held_logs() = Some(Vec::new());
+        HeldLogs
     }
-    if let Some(dp) = dp.next() {
-        data.incidence_increase = data.incidence - dp.incidence_calculated;
-        data.cases_increase = data.cases - dp.cases.total;
-        data.deaths_increase = data.deaths - dp.deaths.total;
-        data.hospitalisations_increase = data.hospitalisations - dp.hospitalisations.total;
-        data.recoveries_increase = data.recoveries - dp.recoveries.total;
+}
+
+impl Drop for HeldLogs {
+    fn drop(&mut self) {
+        if let Some(logs) = held_logs().take() {
+            let mut stderr = std::io::stderr().lock();
+            let _ = stderr.write_all(&logs);
+            if logs.len() >= MAX_HELD_LOGS {
+                let _ = writeln!(
+                    stderr,
+                    "Left out the later logs, as more than {} bytes were held back",
+                    MAX_HELD_LOGS
+                );
+            }
+        }
     }
+}
 
-    data
-}
-
-#[derive(Debug, Default)]
-struct SummarizedData {
-    recoveries: u32,
-    recoveries_increase: u32,
-    hospitalisations: u32,
-    hospitalisations_increase: u32,
-    deaths: u32,
-    deaths_increase: u32,
-    cases: u32,
-    cases_increase: u32,
-    incidence: f64,
-    incidence_increase: f64,
-}
-
-impl Display for SummarizedData {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "incidence: {}", self.incidence)?;
-        writeln!(f, "incidence_increase: {}", self.incidence_increase)?;
-        writeln!(f, "cases: {}", self.cases)?;
-        writeln!(f, "cases_increase: {}", self.cases_increase)?;
-        writeln!(f, "deaths: {}", self.deaths)?;
-        writeln!(f, "deaths_increase: {}", self.deaths_increase)?;
-        writeln!(f, "hospitalisations: {}", self.hospitalisations)?;
-        writeln!(
-            f,
-            "hospitalisations_increase: {}",
-            self.hospitalisations_increase
-        )?;
-        writeln!(f, "recoveries: {}", self.recoveries)?;
-        writeln!(f, "recoveries_increase: {}", self.recoveries_increase)
+fn held_logs() -> MutexGuard<'static, Option<Vec<u8>>> {
+    HELD_LOGS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Writes the logs to stderr, unless they are held back, stdout is left to the output
+struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match held_logs().as_mut() {
+            Some(held) => {
+                if held.len() < MAX_HELD_LOGS {
+                    held.extend_from_slice(buf);
+                }
+                Ok(buf.len())
+            }
+            None => std::io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        std::io::stderr().flush()
     }
 }
 
@@ -237,7 +823,7 @@ fn install_tracing(verbosity: i8) {
     use tracing_subscriber::prelude::*;
     use tracing_subscriber::{fmt, EnvFilter};
 
-    let fmt_layer = fmt::layer().with_target(true);
+    let fmt_layer = fmt::layer().with_target(true).with_writer(|| LogWriter);
     let filter_layer = EnvFilter::try_from_env("COVIDD_LOG")
         .or_else(|_| EnvFilter::try_new(verbosity_to_level(verbosity)))
         .unwrap();
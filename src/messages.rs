@@ -1,3 +1,6 @@
+use crate::history::{Context, Metric, Notable};
+use chrono::{DateTime, NaiveDate, Utc};
+
 pub struct Messages {
     bundle: Box<dyn Bundle>,
 }
@@ -19,6 +22,22 @@ impl Messages {
     ) -> String {
         self.bundle.get(msg, count.into(), increase.map(Into::into))
     }
+
+    pub fn context(&self, context: &Context) -> String {
+        self.bundle.context(context)
+    }
+
+    pub fn as_of(&self, date: NaiveDate, fetched_at: DateTime<Utc>) -> String {
+        self.bundle.as_of(date, fetched_at)
+    }
+
+    pub fn refreshing(&self, fetched_at: DateTime<Utc>) -> String {
+        self.bundle.refreshing(fetched_at)
+    }
+
+    pub fn refresh_failed(&self, fetched_at: DateTime<Utc>, reason: &str) -> String {
+        self.bundle.refresh_failed(fetched_at, reason)
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -29,12 +48,38 @@ pub enum MsgId {
     Active,
     Cases,
     Incidence,
+    ProjectedCases,
+    ProjectedIncidence,
+    ProjectedCasesRange,
+    ProjectedIncidenceRange,
+    NowcastIncidence,
+    NowcastRange,
+    Trend,
+    CaseFatalityRate,
+    HospitalisationRate,
+    LaggedCaseFatalityRate,
+    WavePeaks,
+    Wave,
+    Year,
+    NewCases,
+    LaggedHospitalisations,
+    LaggedDeaths,
 }
 
 pub trait Bundle {
     fn get(&self, msg: MsgId, count: f64, increase: Option<f64>) -> String;
+
+    fn context(&self, context: &Context) -> String;
+
+    fn as_of(&self, date: NaiveDate, fetched_at: DateTime<Utc>) -> String;
+
+    fn refreshing(&self, fetched_at: DateTime<Utc>) -> String;
+
+    fn refresh_failed(&self, fetched_at: DateTime<Utc>, reason: &str) -> String;
 }
 
+const DATE_FORMAT: &str = "%d.%m.%Y";
+
 struct BundleDe;
 
 impl Bundle for BundleDe {
@@ -48,6 +93,22 @@ impl Bundle for BundleDe {
                 MsgId::Active       => format!(  "{:>6} ({:>+5}) Active Fälle",            count, inc),
                 MsgId::Cases        => format!(  "{:>6} ({:>+5}) Fälle",                   count, inc),
                 MsgId::Incidence    => format!("{:>6.1} ({:>+5.1}) Inzidenz",              count, inc),
+                MsgId::ProjectedCases     => format!(  "{:>6.0} ({:>+5.0}) Fälle (Prognose)",    count, inc),
+                MsgId::ProjectedIncidence => format!("{:>6.1} ({:>+5.1}) Inzidenz (Prognose)", count, inc),
+                MsgId::ProjectedCasesRange     => format!("{:>6.0} bis {:>6.0} Fälle (Prognosebereich)",      count, inc),
+                MsgId::ProjectedIncidenceRange => format!("{:>6.1} bis {:>5.1} Inzidenz (Prognosebereich)", count, inc),
+                MsgId::NowcastIncidence   => format!("{:>6.1} ({:>+5.1}) Inzidenz (Nowcast)",  count, inc),
+                MsgId::NowcastRange       => format!("{:>6.1} bis {:>5.1} Inzidenz (Nowcast-Bereich)", count, inc),
+                MsgId::Trend              => format!("{:>6.1} ({:>+5.1}) Fälle pro Tag (Trend)", count, inc),
+                MsgId::CaseFatalityRate       => format!("{:>5.2}% ({:>+5.2}) Fallsterblichkeit",                 count, inc),
+                MsgId::HospitalisationRate    => format!("{:>5.2}% ({:>+5.2}) Hospitalisierungsrate",             count, inc),
+                MsgId::LaggedCaseFatalityRate => format!("{:>5.2}% ({:>+5.2}) Fallsterblichkeit (zeitversetzt)",  count, inc),
+                MsgId::WavePeaks              => format!(  "{:>6} ({:>+5}) Wellenhöhepunkte",                     count, inc),
+                MsgId::Wave                   => format!(  "Welle {:<2} (Höhepunkt {:>6.1})",                      count, inc),
+                MsgId::Year                   => format!(  "{:>6} ({:>6.1} am selben Tag)",                        count, inc),
+                MsgId::NewCases               => format!("{:>6.1} ({:>+5.1}) neue Fälle (Wochenmittel)",          count, inc),
+                MsgId::LaggedHospitalisations => format!(  "Krankenhauseinweisungen {:>3} Tage später (r = {:.2})", count, inc),
+                MsgId::LaggedDeaths           => format!(  "Sterbefälle {:>3} Tage später (r = {:.2})",             count, inc),
             }
             None => match msg {
                 MsgId::Recovered    => format!(  "{:>6} Genesene"               , count),
@@ -56,9 +117,74 @@ impl Bundle for BundleDe {
                 MsgId::Active       => format!(  "{:>6} Active Fälle"           , count),
                 MsgId::Cases        => format!(  "{:>6} Fälle"                  , count),
                 MsgId::Incidence    => format!("{:>6.1} Inzidenz"               , count),
+                MsgId::ProjectedCases     => format!(  "{:>6.0} Fälle (Prognose)"   , count),
+                MsgId::ProjectedIncidence => format!("{:>6.1} Inzidenz (Prognose)", count),
+                MsgId::ProjectedCasesRange     => format!("{:>6.0} Fälle (Prognosebereich)",    count),
+                MsgId::ProjectedIncidenceRange => format!("{:>6.1} Inzidenz (Prognosebereich)", count),
+                MsgId::NowcastIncidence   => format!("{:>6.1} Inzidenz (Nowcast)",  count),
+                MsgId::NowcastRange       => format!("{:>6.1} Inzidenz (Nowcast-Bereich)", count),
+                MsgId::Trend              => format!("{:>6.1} Fälle pro Tag (Trend)", count),
+                MsgId::CaseFatalityRate       => format!("{:>5.2}% Fallsterblichkeit"               , count),
+                MsgId::HospitalisationRate    => format!("{:>5.2}% Hospitalisierungsrate"           , count),
+                MsgId::LaggedCaseFatalityRate => format!("{:>5.2}% Fallsterblichkeit (zeitversetzt)", count),
+                MsgId::WavePeaks              => format!(  "{:>6} Wellenhöhepunkte"                 , count),
+                MsgId::Wave                   => format!(  "Welle {:<2}"                            , count),
+                MsgId::Year                   => format!(  "{:>6}"                                  , count),
+                MsgId::NewCases               => format!("{:>6.1} neue Fälle (Wochenmittel)"        , count),
+                MsgId::LaggedHospitalisations => format!(  "Krankenhauseinweisungen {:>3} Tage später", count),
+                MsgId::LaggedDeaths           => format!(  "Sterbefälle {:>3} Tage später"          , count),
             }
         }
     }
+
+    #[rustfmt::skip]
+    fn context(&self, context: &Context) -> String {
+        let metric = match context.metric {
+            Metric::Incidence        => "Inzidenz",
+            Metric::Cases            => "Neue Fälle",
+            Metric::Deaths           => "Neue Sterbefälle",
+            Metric::Hospitalisations => "Neue Krankenhauseinweisungen",
+            Metric::Recoveries       => "Neue Genesene",
+        };
+        let notable = match context.notable() {
+            Notable::AllTimeHigh        => String::from("höchster Wert bisher"),
+            Notable::AllTimeLow         => String::from("niedrigster Wert bisher"),
+            Notable::HighestSince(date) => format!("höchster Wert seit {}", date.format(DATE_FORMAT)),
+            Notable::LowestSince(date)  => format!("niedrigster Wert seit {}", date.format(DATE_FORMAT)),
+        };
+        format!(
+            "{:<28} {:>8.1}  Perzentil {:>5.1}  Maximum {:>8.1} am {}  {}",
+            metric,
+            context.value,
+            context.percentile,
+            context.max,
+            context.max_date.format(DATE_FORMAT),
+            notable,
+        )
+    }
+
+    fn as_of(&self, date: NaiveDate, fetched_at: DateTime<Utc>) -> String {
+        format!(
+            "Datenstand zum {}, abgerufen am {} UTC",
+            date.format(DATE_FORMAT),
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
+
+    fn refreshing(&self, fetched_at: DateTime<Utc>) -> String {
+        format!(
+            "Daten vom {} UTC, neue Daten werden geladen …",
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
+
+    fn refresh_failed(&self, fetched_at: DateTime<Utc>, reason: &str) -> String {
+        format!(
+            "Neue Daten konnten nicht geladen werden ({}), Daten vom {} UTC",
+            reason,
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
 }
 
 struct BundleEn;
@@ -74,6 +200,22 @@ impl Bundle for BundleEn {
                 MsgId::Active       => format!(  "{:>6} ({:>+5}) active cases", count, inc),
                 MsgId::Cases        => format!(  "{:>6} ({:>+5}) total cases",  count, inc),
                 MsgId::Incidence    => format!("{:>6.1} ({:>+5.1}) incidence",  count, inc),
+                MsgId::ProjectedCases     => format!(  "{:>6.0} ({:>+5.0}) projected cases",     count, inc),
+                MsgId::ProjectedIncidence => format!("{:>6.1} ({:>+5.1}) projected incidence", count, inc),
+                MsgId::ProjectedCasesRange     => format!("{:>6.0} to {:>6.0} projected cases range",     count, inc),
+                MsgId::ProjectedIncidenceRange => format!("{:>6.1} to {:>5.1} projected incidence range", count, inc),
+                MsgId::NowcastIncidence   => format!("{:>6.1} ({:>+5.1}) nowcast incidence",   count, inc),
+                MsgId::NowcastRange       => format!("{:>6.1} to {:>5.1} nowcast range",       count, inc),
+                MsgId::Trend              => format!("{:>6.1} ({:>+5.1}) cases per day (trend)", count, inc),
+                MsgId::CaseFatalityRate       => format!("{:>5.2}% ({:>+5.2}) case fatality rate",          count, inc),
+                MsgId::HospitalisationRate    => format!("{:>5.2}% ({:>+5.2}) hospitalisation rate",        count, inc),
+                MsgId::LaggedCaseFatalityRate => format!("{:>5.2}% ({:>+5.2}) lagged case fatality rate",   count, inc),
+                MsgId::WavePeaks              => format!(  "{:>6} ({:>+5}) wave peaks",                     count, inc),
+                MsgId::Wave                   => format!(  "wave {:<2} (peak {:>6.1})",                       count, inc),
+                MsgId::Year                   => format!(  "{:>6} ({:>6.1} on the same day)",                 count, inc),
+                MsgId::NewCases               => format!("{:>6.1} ({:>+5.1}) new cases (weekly mean)",        count, inc),
+                MsgId::LaggedHospitalisations => format!(  "hospitalised {:>3} days later (r = {:.2})",       count, inc),
+                MsgId::LaggedDeaths           => format!(  "deaths {:>3} days later (r = {:.2})",             count, inc),
             }
             None => match msg {
                 MsgId::Recovered    => format!(  "{:>6} recovered"    , count),
@@ -82,9 +224,74 @@ impl Bundle for BundleEn {
                 MsgId::Active       => format!(  "{:>6} active cases" , count),
                 MsgId::Cases        => format!(  "{:>6} total cases"  , count),
                 MsgId::Incidence    => format!("{:>6.1} incidence"    , count),
+                MsgId::ProjectedCases     => format!(  "{:>6.0} projected cases"    , count),
+                MsgId::ProjectedIncidence => format!("{:>6.1} projected incidence", count),
+                MsgId::ProjectedCasesRange     => format!("{:>6.0} projected cases range",     count),
+                MsgId::ProjectedIncidenceRange => format!("{:>6.1} projected incidence range", count),
+                MsgId::NowcastIncidence   => format!("{:>6.1} nowcast incidence",   count),
+                MsgId::NowcastRange       => format!("{:>6.1} nowcast range",       count),
+                MsgId::Trend              => format!("{:>6.1} cases per day (trend)", count),
+                MsgId::CaseFatalityRate       => format!("{:>5.2}% case fatality rate"       , count),
+                MsgId::HospitalisationRate    => format!("{:>5.2}% hospitalisation rate"     , count),
+                MsgId::LaggedCaseFatalityRate => format!("{:>5.2}% lagged case fatality rate", count),
+                MsgId::WavePeaks              => format!(  "{:>6} wave peaks"                , count),
+                MsgId::Wave                   => format!(  "wave {:<2}"                      , count),
+                MsgId::Year                   => format!(  "{:>6}"                           , count),
+                MsgId::NewCases               => format!("{:>6.1} new cases (weekly mean)"   , count),
+                MsgId::LaggedHospitalisations => format!(  "hospitalised {:>3} days later"   , count),
+                MsgId::LaggedDeaths           => format!(  "deaths {:>3} days later"         , count),
             }
         }
     }
+
+    #[rustfmt::skip]
+    fn context(&self, context: &Context) -> String {
+        let metric = match context.metric {
+            Metric::Incidence        => "incidence",
+            Metric::Cases            => "new cases",
+            Metric::Deaths           => "new deaths",
+            Metric::Hospitalisations => "new hospitalisations",
+            Metric::Recoveries       => "new recoveries",
+        };
+        let notable = match context.notable() {
+            Notable::AllTimeHigh        => String::from("all-time high"),
+            Notable::AllTimeLow         => String::from("all-time low"),
+            Notable::HighestSince(date) => format!("highest since {}", date.format(DATE_FORMAT)),
+            Notable::LowestSince(date)  => format!("lowest since {}", date.format(DATE_FORMAT)),
+        };
+        format!(
+            "{:<20} {:>8.1}  percentile {:>5.1}  max {:>8.1} on {}  {}",
+            metric,
+            context.value,
+            context.percentile,
+            context.max,
+            context.max_date.format(DATE_FORMAT),
+            notable,
+        )
+    }
+
+    fn as_of(&self, date: NaiveDate, fetched_at: DateTime<Utc>) -> String {
+        format!(
+            "Data as known on {}, fetched on {} UTC",
+            date.format(DATE_FORMAT),
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
+
+    fn refreshing(&self, fetched_at: DateTime<Utc>) -> String {
+        format!(
+            "Data fetched on {} UTC, downloading new data …",
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
+
+    fn refresh_failed(&self, fetched_at: DateTime<Utc>, reason: &str) -> String {
+        format!(
+            "Could not download new data ({}), data fetched on {} UTC",
+            reason,
+            fetched_at.format("%d.%m.%Y %H:%M"),
+        )
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
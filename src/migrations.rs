@@ -0,0 +1,118 @@
+//! Upgrades of cache files that were written by older versions of covidd.
+//!
+//! Every change to the layout of the cache increases [`VERSION`] and adds a
+//! migration from the previous version, which works on the raw JSON.
+
+use crate::Result;
+use color_eyre::eyre::eyre;
+use serde_json::Value;
+use std::fmt::Display;
+use tracing::{debug, instrument};
+
+/// The version of the cache layout that is written
+pub const VERSION: u32 = 4;
+
+/// The migration at index `i` upgrades a cache from version `i + 1`
+const MIGRATIONS: [fn(&mut Value) -> Result<()>; VERSION as usize - 1] =
+    [v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// The cache was written by a newer covidd that might have changed the layout in any way
+#[derive(Debug)]
+pub struct NewerVersion {
+    pub version: u32,
+}
+
+impl Display for NewerVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the cache has version {}, but this covidd only supports up to version {}",
+            self.version, VERSION
+        )
+    }
+}
+
+impl std::error::Error for NewerVersion {}
+
+/// Upgrades the cache to the current version
+#[instrument(skip(data), err)]
+pub fn migrate(mut data: Value) -> Result<Value> {
+    // caches before the versioning have no version field
+    let version = data
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(1, |v| v.max(1) as u32);
+    if version > VERSION {
+        return Err(NewerVersion { version }.into());
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        debug!("Migrating the cache from version {}", from + 1);
+        migration(&mut data)?;
+    }
+    Ok(data)
+}
+
+/// Adds the version to the envelope, the data points are unchanged
+fn v1_to_v2(data: &mut Value) -> Result<()> {
+    envelope(data)?.insert(String::from("version"), Value::from(2));
+    Ok(())
+}
+
+/// Adds the population to the envelope, it is unknown for the older caches
+fn v2_to_v3(data: &mut Value) -> Result<()> {
+    let envelope = envelope(data)?;
+    envelope.insert(String::from("version"), Value::from(3));
+    envelope.insert(String::from("population"), Value::Null);
+    Ok(())
+}
+
+/// Adds the checksum of the data points to the envelope, the older caches have none
+fn v3_to_v4(data: &mut Value) -> Result<()> {
+    let envelope = envelope(data)?;
+    envelope.insert(String::from("version"), Value::from(4));
+    envelope.insert(String::from("checksum"), Value::Null);
+    Ok(())
+}
+
+fn envelope(data: &mut Value) -> Result<&mut serde_json::Map<String, Value>> {
+    data.as_object_mut()
+        .ok_or_else(|| eyre!("the cache is not a JSON object"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn unversioned_cache_is_migrated_to_the_current_version() {
+        let data = json!({
+            "created_at": "2021-11-20T08:00:00Z",
+            "attributes": [],
+        });
+
+        let data = migrate(data).unwrap();
+
+        assert_eq!(data["version"], json!(VERSION));
+        assert_eq!(data["population"], Value::Null);
+        assert_eq!(data["checksum"], Value::Null);
+        assert_eq!(data["created_at"], json!("2021-11-20T08:00:00Z"));
+        assert_eq!(data["attributes"], json!([]));
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let data = json!({ "version": VERSION + 1, "attributes": [] });
+
+        let error = migrate(data).unwrap_err();
+
+        let newer = error.downcast_ref::<NewerVersion>().unwrap();
+        assert_eq!(newer.version, VERSION + 1);
+    }
+
+    #[test]
+    fn cache_must_be_an_object() {
+        assert!(migrate(json!([1, 2, 3])).is_err());
+    }
+}
@@ -0,0 +1,332 @@
+use crate::{data::DataPoint, output::Record, projection::Estimate, summary::format_date};
+use chrono::{Date, Utc};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+use tracing::{debug, instrument};
+
+/// Number of days after which the reported cases are considered final
+pub const DEFAULT_MAX_DELAY: usize = 14;
+
+/// Delays with fewer observations than this are not estimated
+const MIN_SAMPLES: usize = 3;
+
+/// Quantiles of the observed completeness that bound the nowcast
+const LOWER_QUANTILE: f64 = 0.1;
+const UPPER_QUANTILE: f64 = 0.9;
+
+/// The reported cases are at most multiplied by the inverse of this
+const MIN_COMPLETENESS: f64 = 0.1;
+
+/// The share of the final number of reported cases that is known after some days
+#[derive(Debug, Copy, Clone)]
+pub struct Completeness {
+    pub mean: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// The completeness for every delay in days, starting with the latest day of a snapshot
+#[derive(Debug, Clone)]
+pub struct Delays(pub Vec<Completeness>);
+
+#[derive(Debug, Copy, Clone)]
+pub struct NowcastPoint {
+    pub date: Date<Utc>,
+    pub reported_cases: Estimate,
+    pub incidence: Estimate,
+}
+
+/// Estimates the reporting delay by comparing what the older snapshots knew
+/// about a day to what the newest snapshot knows about it.
+///
+/// Snapshots are ordered from the oldest to the newest. Only days that are
+/// at least `max_delay` days old in the newest snapshot are compared.
+/// Returns `None` if not even the latest day has enough observations.
+#[instrument(skip(snapshots))]
+pub fn delays(snapshots: &[Vec<DataPoint>], max_delay: usize) -> Option<Delays> {
+    let (newest, older) = snapshots.split_last()?;
+    let newest_date = newest.last()?.dates.date;
+    let settled = newest
+        .iter()
+        .filter(|d| (newest_date - d.dates.date).num_days() >= max_delay as i64)
+        .map(|d| (d.dates.date, f64::from(d.cases.reported)))
+        .collect::<HashMap<_, _>>();
+
+    let mut samples = vec![Vec::new(); max_delay];
+    let mut seen = HashSet::new();
+    for snapshot in older.iter().rev() {
+        let last = match snapshot.last() {
+            Some(last) => last.dates.date,
+            None => continue,
+        };
+        // several downloads on the same day would count the same revisions twice
+        if last == newest_date || !seen.insert(last) {
+            continue;
+        }
+        for d in snapshot.iter().rev() {
+            let delay = (last - d.dates.date).num_days() as usize;
+            if delay >= max_delay {
+                break;
+            }
+            if let Some(&settled) = settled.get(&d.dates.date).filter(|&&s| s > 0.0) {
+                samples[delay].push((f64::from(d.cases.reported), settled));
+            }
+        }
+    }
+
+    let delays = samples
+        .into_iter()
+        .map(|samples| {
+            // a day without any reported cases yet tells nothing about how many are missing
+            let mut ratios = samples
+                .iter()
+                .map(|(r, s)| r / s)
+                .filter(|ratio| ratio.is_finite() && *ratio > 0.0)
+                .collect::<Vec<_>>();
+            if ratios.len() < MIN_SAMPLES {
+                return None;
+            }
+            ratios.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+            let (reported, settled) = samples
+                .iter()
+                .fold((0.0, 0.0), |(r, s), (reported, settled)| {
+                    (r + reported, s + settled)
+                });
+            Some(Completeness {
+                mean: reported / settled,
+                lower: quantile(&ratios, LOWER_QUANTILE),
+                upper: quantile(&ratios, UPPER_QUANTILE),
+            })
+        })
+        .take_while(Option::is_some)
+        .flatten()
+        .collect::<Vec<_>>();
+    debug!("Estimated the completeness for {} days", delays.len());
+
+    if delays.is_empty() {
+        None
+    } else {
+        Some(Delays(delays))
+    }
+}
+
+/// Corrects the reported cases of the most recent days for their expected
+/// revisions, together with the incidence that is calculated from them
+#[instrument(skip(data_points, delays))]
+pub fn nowcast(data_points: &[DataPoint], delays: &Delays) -> Vec<NowcastPoint> {
+    let last = match data_points.last() {
+        Some(last) => last.dates.date,
+        None => return Vec::new(),
+    };
+
+    let cases = data_points
+        .iter()
+        .map(|d| {
+            let reported = f64::from(d.cases.reported);
+            let delay = (last - d.dates.date).num_days();
+            match usize::try_from(delay)
+                .ok()
+                .and_then(|delay| delays.0.get(delay))
+            {
+                // a lower completeness means more missing cases
+                Some(c) => Estimate {
+                    value: reported / c.mean.max(MIN_COMPLETENESS),
+                    lower: reported / c.upper.max(MIN_COMPLETENESS),
+                    upper: reported / c.lower.max(MIN_COMPLETENESS),
+                },
+                None => Estimate {
+                    value: reported,
+                    lower: reported,
+                    upper: reported,
+                },
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let first = data_points.len().saturating_sub(delays.0.len());
+    (first..data_points.len())
+        .map(|i| {
+            let d = &data_points[i];
+            // the incidence of a day is calculated from the cases of the 7 days before it
+            let window = i.saturating_sub(7)..i;
+            let reported = data_points[window.clone()]
+                .iter()
+                .map(|d| f64::from(d.cases.reported))
+                .sum::<f64>();
+            let scale = |estimate: fn(&Estimate) -> f64| {
+                let corrected = cases[window.clone()].iter().map(estimate).sum::<f64>();
+                if reported > 0.0 {
+                    d.incidence_calculated * corrected / reported
+                } else {
+                    d.incidence_calculated
+                }
+            };
+            NowcastPoint {
+                date: d.dates.date,
+                reported_cases: cases[i],
+                incidence: Estimate {
+                    value: scale(|e| e.value),
+                    lower: scale(|e| e.lower),
+                    upper: scale(|e| e.upper),
+                },
+            }
+        })
+        .collect()
+}
+
+/// Adds the nowcast as columns to the series, they are empty for days without a correction
+pub fn add_to_series(series: &mut [Record], nowcast: &[NowcastPoint]) {
+    let by_date = nowcast
+        .iter()
+        .map(|p| (format_date(p.date), p))
+        .collect::<HashMap<_, _>>();
+    for record in series {
+        let point = record
+            .get("date")
+            .and_then(|date| date.as_str())
+            .and_then(|date| by_date.get(date))
+            .copied();
+        record.push(
+            "cases_reported_nowcast",
+            point.map(|p| p.reported_cases.value),
+        );
+        record.push(
+            "cases_reported_nowcast_lower",
+            point.map(|p| p.reported_cases.lower),
+        );
+        record.push(
+            "cases_reported_nowcast_upper",
+            point.map(|p| p.reported_cases.upper),
+        );
+        record.push("incidence_nowcast", point.map(|p| p.incidence.value));
+        record.push("incidence_nowcast_lower", point.map(|p| p.incidence.lower));
+        record.push("incidence_nowcast_upper", point.map(|p| p.incidence.upper));
+    }
+}
+
+/// Linear interpolation between the closest ranks of sorted values
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let rank = q * (sorted.len() - 1) as f64;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - rank.floor())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::Datelike;
+
+    const MAX_DELAY: usize = 3;
+
+    fn data_point(day: u32, reported: u32) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.reported = reported;
+        data_point.incidence_calculated = 70.0;
+        data_point
+    }
+
+    /// A download on the given day, which knows half of the cases of that day
+    /// and 80% of the cases of the day before
+    fn snapshot(last: u32) -> Vec<DataPoint> {
+        snapshot_with_latest(last, 50)
+    }
+
+    fn snapshot_with_latest(last: u32, latest: u32) -> Vec<DataPoint> {
+        (1..=last)
+            .map(|day| match last - day {
+                0 => data_point(day, latest),
+                1 => data_point(day, 80),
+                _ => data_point(day, 100),
+            })
+            .collect()
+    }
+
+    fn snapshots() -> Vec<Vec<DataPoint>> {
+        let newest = (1..=20).map(|day| data_point(day, 100)).collect();
+        vec![
+            snapshot(10),
+            snapshot(11),
+            snapshot(12),
+            snapshot(13),
+            newest,
+        ]
+    }
+
+    #[test]
+    fn completeness_of_the_older_snapshots() {
+        let delays = delays(&snapshots(), MAX_DELAY).unwrap();
+
+        let means = delays.0.iter().map(|c| c.mean).collect::<Vec<_>>();
+        assert_eq!(means, vec![0.5, 0.8, 1.0]);
+        assert_eq!(delays.0[0].lower, 0.5);
+        assert_eq!(delays.0[0].upper, 0.5);
+    }
+
+    #[test]
+    fn days_without_reported_cases_are_left_out() {
+        let mut snapshots = snapshots();
+        snapshots.insert(4, snapshot_with_latest(14, 0));
+
+        let delays = delays(&snapshots, MAX_DELAY).unwrap();
+
+        assert_eq!(delays.0[0].mean, 200.0 / 500.0);
+        assert_eq!(delays.0[0].lower, 0.5);
+        assert_eq!(delays.0[0].upper, 0.5);
+    }
+
+    #[test]
+    fn no_delays_without_reported_cases() {
+        let snapshots = vec![
+            snapshot_with_latest(10, 0),
+            snapshot_with_latest(11, 0),
+            snapshot_with_latest(12, 0),
+            (1..=20).map(|day| data_point(day, 100)).collect(),
+        ];
+
+        assert!(delays(&snapshots, MAX_DELAY).is_none());
+    }
+
+    #[test]
+    fn not_enough_snapshots_for_a_delay() {
+        let mut snapshots = snapshots();
+        snapshots.drain(..2);
+
+        assert!(delays(&snapshots, MAX_DELAY).is_none());
+    }
+
+    #[test]
+    fn recent_days_are_corrected() {
+        let delays = delays(&snapshots(), MAX_DELAY).unwrap();
+
+        let nowcast = nowcast(&snapshot(10), &delays);
+
+        let dates = nowcast.iter().map(|p| p.date.day()).collect::<Vec<_>>();
+        assert_eq!(dates, vec![8, 9, 10]);
+        let cases = nowcast
+            .iter()
+            .map(|p| p.reported_cases.value)
+            .collect::<Vec<_>>();
+        assert_eq!(cases, vec![100.0, 100.0, 100.0]);
+        assert_eq!(nowcast[0].incidence.value, 70.0);
+        assert_eq!(nowcast[2].incidence.value, 70.0 * 700.0 / 680.0);
+    }
+
+    #[test]
+    fn correction_is_bounded() {
+        let delays = Delays(vec![Completeness {
+            mean: 0.0,
+            lower: 0.0,
+            upper: 0.0,
+        }]);
+
+        let nowcast = nowcast(&[data_point(1, 50)], &delays);
+
+        assert_eq!(nowcast[0].reported_cases.upper, 500.0);
+    }
+}
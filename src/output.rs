@@ -0,0 +1,308 @@
+use crate::{data::DataPoint, rates, seasonal, Result};
+use clap::ValueEnum;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+use serde_json::Value;
+use std::{fmt::Display, io::Write};
+use tracing::instrument;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// `key: value` lines for the summary, tab separated values for the series
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+}
+
+/// Named values in a fixed order, with stable English names that are
+/// independent of the upstream schema
+#[derive(Debug, Default, Clone)]
+pub struct Record(Vec<(String, Value)>);
+
+impl Record {
+    pub fn push(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.0.push((key.into(), value.into()));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> + '_ {
+        self.0.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.0.iter().map(|(key, _)| key.as_str())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, value)| value)
+    }
+
+    /// A record with only the given keys, in that order
+    pub fn select<'a>(&self, keys: impl IntoIterator<Item = &'a str>) -> Record {
+        let mut record = Record::default();
+        for key in keys {
+            if let Some(value) = self.get(key) {
+                record.push(key, value.clone());
+            }
+        }
+        record
+    }
+
+    fn values(&self) -> impl Iterator<Item = Text<'_>> + '_ {
+        self.0.iter().map(|(_, value)| Text(value))
+    }
+}
+
+impl Serialize for Record {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.0.len()))?;
+        for (key, value) in &self.0 {
+            map.serialize_entry(key, value)?;
+        }
+        map.end()
+    }
+}
+
+/// A value as plain text, missing values are empty
+pub struct Text<'a>(pub &'a Value);
+
+impl Display for Text<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Value::Null => Ok(()),
+            Value::String(s) => f.write_str(s),
+            value => value.fmt(f),
+        }
+    }
+}
+
+/// One record per data point with the corrected values and the derived rates
+#[instrument(skip(data_points))]
+pub fn series(data_points: &[DataPoint], rates: rates::Config) -> Vec<Record> {
+    let decomposition = seasonal::decompose(data_points);
+    let adjusted = decomposition
+        .as_ref()
+        .map(|decomposition| seasonal::adjusted_incidence(data_points, decomposition));
+    let rates = rates::rates(data_points, rates);
+
+    data_points
+        .iter()
+        .enumerate()
+        .map(|(i, d)| {
+            let mut record = Record::default();
+            record.push("date", d.dates.date.format("%Y-%m-%d").to_string());
+            record.push("incidence", d.incidence_calculated);
+            record.push("incidence_reported", d.incidence);
+            record.push(
+                "incidence_weekday_adjusted",
+                adjusted.as_ref().and_then(|a| a.get(i).copied()),
+            );
+            record.push("cases", d.cases.total);
+            record.push("cases_increase", d.cases.increase);
+            record.push("cases_reported", d.cases.reported);
+            let decomposition = decomposition.as_ref();
+            record.push("cases_trend", decomposition.and_then(|d| d.trend[i]));
+            record.push("cases_weekday_factor", decomposition.map(|d| d.weekday[i]));
+            record.push("cases_residual", decomposition.and_then(|d| d.residual[i]));
+            record.push(
+                "cases_weekday_adjusted",
+                decomposition.map(|d| d.adjusted[i]),
+            );
+            record.push("active_cases", d.active_cases());
+            record.push("deaths", d.deaths.total);
+            record.push("deaths_increase", d.deaths.increase);
+            record.push("hospitalisations", d.hospitalisations.total);
+            record.push("hospitalisations_increase", d.hospitalisations.increase);
+            record.push("beds_in_use", d.hospitalisations.beds_in_use);
+            record.push("recoveries", d.recoveries.total);
+            record.push("recoveries_increase", d.recoveries.increase);
+            record.push("case_fatality_rate", rates.case_fatality[i]);
+            record.push("hospitalisation_rate", rates.hospitalisation[i]);
+            record.push("lagged_case_fatality_rate", rates.lagged_case_fatality[i]);
+            record
+        })
+        .collect()
+}
+
+pub fn write_one(mut out: impl Write, format: Format, record: &Record) -> Result<()> {
+    match format {
+        Format::Text => {
+            for (key, value) in record.iter() {
+                writeln!(out, "{}: {}", key, Text(value))?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, record)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            serde_json::to_writer(&mut out, record)?;
+            writeln!(out)?;
+        }
+        Format::Yaml => write_yaml(&mut out, record, "")?,
+        Format::Csv => write_csv(out, std::slice::from_ref(record))?,
+    }
+    Ok(())
+}
+
+pub fn write_all(mut out: impl Write, format: Format, records: &[Record]) -> Result<()> {
+    match format {
+        Format::Text => {
+            if let Some(first) = records.first() {
+                writeln!(out, "{}", first.keys().collect::<Vec<_>>().join("\t"))?;
+            }
+            for record in records {
+                let values = record.values().map(|v| v.to_string()).collect::<Vec<_>>();
+                writeln!(out, "{}", values.join("\t"))?;
+            }
+        }
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut out, records)?;
+            writeln!(out)?;
+        }
+        Format::Ndjson => {
+            for record in records {
+                serde_json::to_writer(&mut out, record)?;
+                writeln!(out)?;
+            }
+        }
+        Format::Yaml => {
+            if records.is_empty() {
+                writeln!(out, "[]")?;
+            }
+            for record in records {
+                write_yaml(&mut out, record, "- ")?;
+            }
+        }
+        Format::Csv => write_csv(out, records)?,
+    }
+    Ok(())
+}
+
+fn write_csv(out: impl Write, records: &[Record]) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(out);
+    if let Some(first) = records.first() {
+        writer.write_record(first.keys())?;
+    }
+    for record in records {
+        writer.write_record(record.values().map(|v| v.to_string()))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Writes a record as a YAML mapping, the first line prefixed with `first`
+/// and all following lines indented to match it.
+///
+/// Records only contain scalars, which are written as JSON, a subset of YAML.
+fn write_yaml(mut out: impl Write, record: &Record, first: &str) -> Result<()> {
+    let indent = " ".repeat(first.len());
+    for (i, (key, value)) in record.iter().enumerate() {
+        let prefix = if i == 0 { first } else { &indent };
+        writeln!(out, "{}{}: {}", prefix, key, value)?;
+    }
+    if record.0.is_empty() {
+        writeln!(out, "{}{{}}", first)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    fn record() -> Record {
+        let mut record = Record::default();
+        record.push("text", "a, \"b\"\nc");
+        record.push("missing", Value::Null);
+        record.push("number", 1.5);
+        record
+    }
+
+    fn written(write: impl FnOnce(&mut Vec<u8>) -> Result<()>) -> String {
+        let mut out = Vec::new();
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv_quotes_and_escapes_strings() {
+        let csv = written(|out| write_all(out, Format::Csv, &[record()]));
+
+        assert_eq!(csv, "text,missing,number\n\"a, \"\"b\"\"\nc\",,1.5\n");
+    }
+
+    #[test]
+    fn yaml_quotes_and_escapes_strings() {
+        let yaml = written(|out| write_all(out, Format::Yaml, &[record(), record()]));
+
+        assert_eq!(
+            yaml,
+            "- text: \"a, \\\"b\\\"\\nc\"\n  missing: null\n  number: 1.5\n".repeat(2)
+        );
+    }
+
+    #[test]
+    fn yaml_without_records() {
+        assert_eq!(written(|out| write_all(out, Format::Yaml, &[])), "[]\n");
+        assert_eq!(
+            written(|out| write_one(out, Format::Yaml, &Record::default())),
+            "{}\n"
+        );
+    }
+
+    #[test]
+    fn missing_values_are_empty_text() {
+        let text = written(|out| write_one(out, Format::Text, &record()));
+
+        assert_eq!(text, "text: a, \"b\"\nc\nmissing: \nnumber: 1.5\n");
+    }
+
+    #[test]
+    fn missing_values_are_json_null() {
+        let ndjson = written(|out| write_all(out, Format::Ndjson, &[record()]));
+
+        assert_eq!(
+            ndjson,
+            "{\"text\":\"a, \\\"b\\\"\\nc\",\"missing\":null,\"number\":1.5}\n"
+        );
+    }
+
+    #[test]
+    fn series_field_order_is_stable() {
+        let records = series(&[fixtures::day(1)], rates::Config::default());
+
+        assert_eq!(
+            records[0].keys().collect::<Vec<_>>(),
+            vec![
+                "date",
+                "incidence",
+                "incidence_reported",
+                "incidence_weekday_adjusted",
+                "cases",
+                "cases_increase",
+                "cases_reported",
+                "cases_trend",
+                "cases_weekday_factor",
+                "cases_residual",
+                "cases_weekday_adjusted",
+                "active_cases",
+                "deaths",
+                "deaths_increase",
+                "hospitalisations",
+                "hospitalisations_increase",
+                "beds_in_use",
+                "recoveries",
+                "recoveries_increase",
+                "case_fatality_rate",
+                "hospitalisation_rate",
+                "lagged_case_fatality_rate",
+            ]
+        );
+        assert_eq!(records[0].get("date"), Some(&Value::from("2021-11-01")));
+        assert_eq!(records[0].get("cases_trend"), Some(&Value::Null));
+    }
+}
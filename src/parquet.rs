@@ -0,0 +1,221 @@
+//! Writes the exported records as Parquet, with one optional column per field
+//! in a single row group, which is plenty for a few thousand rows.
+
+use crate::{output::Record, Result};
+use ::parquet::{
+    basic::{LogicalType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    schema::types::Type,
+};
+use chrono::NaiveDate;
+use color_eyre::eyre::eyre;
+use serde_json::Value;
+use std::{io::Write, sync::Arc};
+use tracing::{debug, instrument};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Date,
+    Integer,
+    Double,
+    Text,
+}
+
+impl Kind {
+    /// The narrowest kind that fits all values of a column, columns without
+    /// any values are doubles, since they are missing rates
+    fn of<'a>(name: &str, values: impl Iterator<Item = &'a Value>) -> Result<Self> {
+        let mut kind = None;
+        for value in values {
+            let this = match value {
+                Value::Null => continue,
+                Value::String(s) if parse_date(s).is_some() => Kind::Date,
+                Value::String(_) => Kind::Text,
+                Value::Number(n) if n.is_f64() => Kind::Double,
+                Value::Number(_) => Kind::Integer,
+                _ => return Err(eyre!("Unsupported value in column {}: {}", name, value)),
+            };
+            kind = match (kind, this) {
+                (None, this) => Some(this),
+                (Some(Kind::Integer), Kind::Double) | (Some(Kind::Double), Kind::Integer) => {
+                    Some(Kind::Double)
+                }
+                (Some(kind), this) if kind == this => Some(kind),
+                (Some(kind), this) => {
+                    return Err(eyre!(
+                        "Mixed values in column {}: {:?} and {:?}",
+                        name,
+                        kind,
+                        this
+                    ))
+                }
+            };
+        }
+        Ok(kind.unwrap_or(Kind::Double))
+    }
+
+    fn schema(self, name: &str) -> Result<Type> {
+        let (physical_type, logical_type) = match self {
+            Kind::Date => (PhysicalType::INT32, Some(LogicalType::Date)),
+            Kind::Integer => (PhysicalType::INT64, None),
+            Kind::Double => (PhysicalType::DOUBLE, None),
+            Kind::Text => (PhysicalType::BYTE_ARRAY, Some(LogicalType::String)),
+        };
+        Ok(Type::primitive_type_builder(name, physical_type)
+            .with_repetition(Repetition::OPTIONAL)
+            .with_logical_type(logical_type)
+            .build()?)
+    }
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+fn days_since_epoch(value: &Value) -> i32 {
+    value.as_str().and_then(parse_date).map_or(0, |d| {
+        (d - NaiveDate::from_ymd(1970, 1, 1)).num_days() as i32
+    })
+}
+
+#[instrument(skip(out, records))]
+pub fn write(mut out: impl Write, records: &[Record]) -> Result<()> {
+    let names = records
+        .first()
+        .map(|r| r.iter().map(|(key, _)| key.to_string()).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let mut columns = vec![Vec::with_capacity(records.len()); names.len()];
+    for record in records {
+        for (column, (_, value)) in columns.iter_mut().zip(record.iter()) {
+            column.push(value);
+        }
+    }
+
+    let kinds = names
+        .iter()
+        .zip(&columns)
+        .map(|(name, values)| Kind::of(name, values.iter().copied()))
+        .collect::<Result<Vec<_>>>()?;
+    let fields = names
+        .iter()
+        .zip(&kinds)
+        .map(|(name, kind)| kind.schema(name).map(Arc::new))
+        .collect::<Result<Vec<_>>>()?;
+    let schema = Type::group_type_builder("schema")
+        .with_fields(fields)
+        .build()?;
+    let properties = WriterProperties::builder()
+        .set_created_by(concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION")).into())
+        .build();
+
+    // the writer needs to own a `Send` sink, which a locked stdout is not
+    let mut file = Vec::new();
+    let mut writer = SerializedFileWriter::new(&mut file, Arc::new(schema), Arc::new(properties))?;
+    let mut row_group = writer.next_row_group()?;
+    for ((name, values), kind) in names.iter().zip(&columns).zip(&kinds) {
+        debug!("Writing column {} as {:?}", name, kind);
+        let mut column = row_group
+            .next_column()?
+            .ok_or_else(|| eyre!("Missing column {} in the Parquet schema", name))?;
+
+        let levels = values
+            .iter()
+            .map(|v| i16::from(!v.is_null()))
+            .collect::<Vec<_>>();
+        let present = values.iter().filter(|v| !v.is_null());
+        match column.untyped() {
+            ColumnWriter::Int32ColumnWriter(w) => {
+                let values = present.map(|v| days_since_epoch(v)).collect::<Vec<_>>();
+                w.write_batch(&values, Some(&levels), None)?
+            }
+            ColumnWriter::Int64ColumnWriter(w) => {
+                let values = present
+                    .map(|v| v.as_i64().unwrap_or_default())
+                    .collect::<Vec<_>>();
+                w.write_batch(&values, Some(&levels), None)?
+            }
+            ColumnWriter::DoubleColumnWriter(w) => {
+                let values = present
+                    .map(|v| v.as_f64().unwrap_or_default())
+                    .collect::<Vec<_>>();
+                w.write_batch(&values, Some(&levels), None)?
+            }
+            ColumnWriter::ByteArrayColumnWriter(w) => {
+                let values = present
+                    .map(|v| ByteArray::from(v.as_str().unwrap_or_default()))
+                    .collect::<Vec<_>>();
+                w.write_batch(&values, Some(&levels), None)?
+            }
+            _ => return Err(eyre!("Unexpected type of column {}", name)),
+        };
+        column.close()?;
+    }
+    row_group.close()?;
+    writer.close()?;
+
+    out.write_all(&file)?;
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::Field,
+    };
+    use std::fs::{self, File};
+
+    fn record(date: &str, cases: u32, rate: Option<f64>) -> Record {
+        let mut record = Record::default();
+        record.push("date", date);
+        record.push("cases", cases);
+        record.push("case_fatality_rate", rate);
+        record.push("missing", None::<f64>);
+        record
+    }
+
+    #[test]
+    fn written_file_is_read_back() {
+        let records = [
+            record("2021-11-01", 100, None),
+            record("2021-11-02", 120, Some(1.5)),
+            record("2021-11-03", 90, None),
+        ];
+        let file = std::env::temp_dir().join(format!("covidd-parquet-{}", std::process::id()));
+        write(File::create(&file).unwrap(), &records).unwrap();
+
+        let reader = SerializedFileReader::new(File::open(&file).unwrap()).unwrap();
+        let rows = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.unwrap()
+                    .get_column_iter()
+                    .map(|(name, field)| (name.clone(), field.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        fs::remove_file(file).unwrap();
+
+        let row = |date, cases, rate| {
+            vec![
+                (String::from("date"), Field::Date(date)),
+                (String::from("cases"), Field::Long(cases)),
+                (String::from("case_fatality_rate"), rate),
+                (String::from("missing"), Field::Null),
+            ]
+        };
+        assert_eq!(
+            rows,
+            vec![
+                row(18932, 100, Field::Null),
+                row(18933, 120, Field::Double(1.5)),
+                row(18934, 90, Field::Null),
+            ]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{data::DataPoint, resample};
+use chrono::prelude::*;
+use tracing::{debug, instrument};
+
+/// z-score for a two-sided 95% prediction interval
+const Z_95: f64 = 1.96;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Number of trailing data points to fit
+    pub window: usize,
+    /// Number of data points to project
+    pub horizon: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Projection {
+    pub points: Vec<ProjectedPoint>,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ProjectedPoint {
+    pub date: Date<Utc>,
+    pub incidence: Estimate,
+    pub new_cases: Estimate,
+    pub total_cases: Estimate,
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct Estimate {
+    pub value: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Fits a log-linear trend over the trailing window of the incidence and the
+/// new cases and continues it for the configured horizon.
+///
+/// New cases are smoothed over a week before fitting to remove the weekday
+/// reporting pattern. Returns `None` if there are not enough positive values
+/// in the window to fit a trend.
+#[instrument(skip(data_points))]
+pub fn project(data_points: &[DataPoint], config: Config) -> Option<Projection> {
+    let last = data_points.last()?;
+    let step = resample::step_days(data_points);
+    let smoothing = (7 / step).max(1) as usize;
+
+    let window_start = data_points.len().saturating_sub(config.window);
+    let incidences = data_points[window_start..]
+        .iter()
+        .map(|d| d.incidence_calculated)
+        .collect::<Vec<_>>();
+    let new_cases = (window_start..data_points.len())
+        .map(|i| {
+            let from = (i + 1).saturating_sub(smoothing);
+            let days = &data_points[from..=i];
+            days.iter()
+                .map(|d| f64::from(d.cases.reported))
+                .sum::<f64>()
+                / days.len() as f64
+        })
+        .collect::<Vec<_>>();
+
+    let incidence_fit = LogLinearFit::fit(&incidences)?;
+    let cases_fit = LogLinearFit::fit(&new_cases)?;
+    debug!(
+        "Projection growth per step: incidence={:.4}, cases={:.4}",
+        incidence_fit.slope.exp(),
+        cases_fit.slope.exp()
+    );
+
+    let mut total_cases = Estimate {
+        value: f64::from(last.cases.total),
+        lower: f64::from(last.cases.total),
+        upper: f64::from(last.cases.total),
+    };
+    let points = (1..=config.horizon)
+        .map(|step_ahead| {
+            let incidence = incidence_fit.predict(incidences.len() - 1 + step_ahead);
+            let new_cases = cases_fit.predict(new_cases.len() - 1 + step_ahead);
+            total_cases.value += new_cases.value;
+            total_cases.lower += new_cases.lower;
+            total_cases.upper += new_cases.upper;
+            ProjectedPoint {
+                date: last.dates.date + chrono::Duration::days(step * step_ahead as i64),
+                incidence,
+                new_cases,
+                total_cases,
+            }
+        })
+        .collect();
+
+    Some(Projection { points })
+}
+
+/// Ordinary least squares fit of `ln(y) = intercept + slope * x`
+#[derive(Debug, Copy, Clone)]
+struct LogLinearFit {
+    intercept: f64,
+    slope: f64,
+    x_mean: f64,
+    x_sum_of_squares: f64,
+    residual_std_error: f64,
+    n: f64,
+}
+
+impl LogLinearFit {
+    fn fit(values: &[f64]) -> Option<Self> {
+        let points = values
+            .iter()
+            .enumerate()
+            .filter(|(_, y)| **y > 0.0)
+            .map(|(x, y)| (x as f64, y.ln()))
+            .collect::<Vec<_>>();
+        if points.len() < 3 {
+            return None;
+        }
+
+        let n = points.len() as f64;
+        let x_mean = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let y_mean = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let x_sum_of_squares = points
+            .iter()
+            .map(|(x, _)| (x - x_mean).powi(2))
+            .sum::<f64>();
+        let xy_sum_of_products = points
+            .iter()
+            .map(|(x, y)| (x - x_mean) * (y - y_mean))
+            .sum::<f64>();
+
+        let slope = xy_sum_of_products / x_sum_of_squares;
+        let intercept = y_mean - slope * x_mean;
+        let residual_sum_of_squares = points
+            .iter()
+            .map(|(x, y)| (y - intercept - slope * x).powi(2))
+            .sum::<f64>();
+        let residual_std_error = (residual_sum_of_squares / (n - 2.0)).sqrt();
+
+        Some(Self {
+            intercept,
+            slope,
+            x_mean,
+            x_sum_of_squares,
+            residual_std_error,
+            n,
+        })
+    }
+
+    fn predict(&self, x: usize) -> Estimate {
+        let x = x as f64;
+        let y = self.intercept + self.slope * x;
+        let std_error = self.residual_std_error
+            * (1.0 + 1.0 / self.n + (x - self.x_mean).powi(2) / self.x_sum_of_squares).sqrt();
+        Estimate {
+            value: y.exp(),
+            lower: (y - Z_95 * std_error).exp(),
+            upper: (y + Z_95 * std_error).exp(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::Duration;
+
+    const CONFIG: Config = Config {
+        window: 5,
+        horizon: 2,
+    };
+
+    /// Data points `step` days apart, growing by `growth` per data point
+    fn growing(count: u32, step: i64, growth: f64) -> Vec<DataPoint> {
+        (0..count)
+            .map(|i| {
+                let mut data_point =
+                    fixtures::on(Utc.ymd(2021, 1, 4) + Duration::days(step * i64::from(i)));
+                let value = 100.0 * growth.powi(i as i32);
+                data_point.incidence_calculated = value;
+                data_point.cases.reported = value.round() as u32;
+                data_point.cases.total = 10_000;
+                data_point
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-6 * expected.abs().max(1.0),
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn exponential_growth_is_continued() {
+        let projection = project(&growing(14, 1, 1.5), CONFIG).unwrap();
+        let first = projection.points[0];
+
+        assert_eq!(projection.points.len(), 2);
+        assert_eq!(first.date, Utc.ymd(2021, 1, 18));
+        assert_close(first.incidence.value, 100.0 * 1.5f64.powi(14));
+        assert_close(
+            projection.points[1].incidence.value,
+            100.0 * 1.5f64.powi(15),
+        );
+    }
+
+    #[test]
+    fn exact_fit_has_no_interval() {
+        let projection = project(&growing(14, 1, 1.5), CONFIG).unwrap();
+        let incidence = projection.points[1].incidence;
+
+        assert_close(incidence.lower, incidence.value);
+        assert_close(incidence.upper, incidence.value);
+    }
+
+    #[test]
+    fn new_cases_are_added_to_the_total() {
+        let projection = project(&growing(14, 1, 1.0), CONFIG).unwrap();
+
+        assert_close(projection.points[0].new_cases.value, 100.0);
+        assert_close(projection.points[1].total_cases.value, 10_200.0);
+    }
+
+    #[test]
+    fn weekly_data_is_not_smoothed() {
+        // smoothing over 7 data points would average the first weeks of the window
+        let projection = project(
+            &growing(4, 7, 2.0),
+            Config {
+                window: 4,
+                ..CONFIG
+            },
+        )
+        .unwrap();
+        let first = projection.points[0];
+
+        assert_eq!(first.date, Utc.ymd(2021, 2, 1));
+        assert_close(first.new_cases.value, 1600.0);
+    }
+
+    #[test]
+    fn too_few_data_points() {
+        assert!(project(&[], CONFIG).is_none());
+        assert!(project(&growing(2, 1, 1.5), CONFIG).is_none());
+    }
+
+    #[test]
+    fn no_projection_without_cases() {
+        let mut data_points = growing(14, 1, 1.5);
+        for data_point in &mut data_points {
+            data_point.cases.reported = 0;
+        }
+
+        assert!(project(&data_points, CONFIG).is_none());
+    }
+}
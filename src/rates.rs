@@ -0,0 +1,140 @@
+use crate::data::DataPoint;
+use tracing::instrument;
+
+#[derive(Debug, Copy, Clone)]
+pub struct Config {
+    /// Number of data points that are summed up for a rate
+    pub window: usize,
+    /// Number of data points that deaths trail cases in the lagged case fatality rate
+    pub lag: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            window: 28,
+            lag: 14,
+        }
+    }
+}
+
+/// Rolling rates in percent, aligned with the data points.
+///
+/// A rate is `None` if the window is not yet filled or there were no new
+/// cases in the window.
+#[derive(Debug, Clone)]
+pub struct Rates {
+    /// Deaths per case over the window
+    pub case_fatality: Vec<Option<f64>>,
+    /// Hospitalisations per case over the window
+    pub hospitalisation: Vec<Option<f64>>,
+    /// Deaths over the window per cases over the window that ended `lag` points earlier
+    pub lagged_case_fatality: Vec<Option<f64>>,
+}
+
+impl Rates {
+    pub fn last(&self) -> (Option<f64>, Option<f64>, Option<f64>) {
+        (
+            self.case_fatality.last().copied().flatten(),
+            self.hospitalisation.last().copied().flatten(),
+            self.lagged_case_fatality.last().copied().flatten(),
+        )
+    }
+}
+
+#[instrument(skip(data_points))]
+pub fn rates(data_points: &[DataPoint], config: Config) -> Rates {
+    let window = config.window.max(1);
+    let rolling_sum = |increase: fn(&DataPoint) -> i32, end: usize| -> Option<i64> {
+        let start = (end + 1).checked_sub(window)?;
+        let sum = data_points[start..=end]
+            .iter()
+            .map(|d| i64::from(increase(d)))
+            .sum();
+        Some(sum)
+    };
+    let rate = |numerator: Option<i64>, denominator: Option<i64>| -> Option<f64> {
+        let denominator = denominator.filter(|d| *d > 0)?;
+        Some(numerator? as f64 * 100.0 / denominator as f64)
+    };
+
+    let cases = |i| rolling_sum(|d| d.cases.increase, i);
+    let deaths = |i| rolling_sum(|d| d.deaths.increase, i);
+    let hospitalisations = |i| rolling_sum(|d| d.hospitalisations.increase, i);
+
+    let case_fatality = (0..data_points.len())
+        .map(|i| rate(deaths(i), cases(i)))
+        .collect();
+    let hospitalisation = (0..data_points.len())
+        .map(|i| rate(hospitalisations(i), cases(i)))
+        .collect();
+    let lagged_case_fatality = (0..data_points.len())
+        .map(|i| rate(deaths(i), i.checked_sub(config.lag).and_then(cases)))
+        .collect();
+
+    Rates {
+        case_fatality,
+        hospitalisation,
+        lagged_case_fatality,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    fn data_point(day: u32, cases: i32, deaths: i32, hospitalisations: i32) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.increase = cases;
+        data_point.deaths.increase = deaths;
+        data_point.hospitalisations.increase = hospitalisations;
+        data_point
+    }
+
+    fn data() -> Vec<DataPoint> {
+        vec![
+            data_point(1, 10, 0, 1),
+            data_point(2, 10, 1, 1),
+            data_point(3, 20, 1, 2),
+            data_point(4, 0, 2, 0),
+        ]
+    }
+
+    const CONFIG: Config = Config { window: 2, lag: 1 };
+
+    #[test]
+    fn rates_over_the_window() {
+        let rates = rates(&data(), CONFIG);
+
+        assert_eq!(
+            rates.case_fatality,
+            vec![None, Some(5.0), Some(200.0 / 30.0), Some(15.0)]
+        );
+        assert_eq!(
+            rates.hospitalisation,
+            vec![None, Some(10.0), Some(10.0), Some(10.0)]
+        );
+    }
+
+    #[test]
+    fn lagged_rate_divides_by_earlier_cases() {
+        let rates = rates(&data(), CONFIG);
+
+        assert_eq!(
+            rates.lagged_case_fatality,
+            vec![None, None, Some(10.0), Some(10.0)]
+        );
+        assert_eq!(rates.last(), (Some(15.0), Some(10.0), Some(10.0)));
+    }
+
+    #[test]
+    fn no_rate_without_cases() {
+        let data = [data_point(1, 0, 1, 1), data_point(2, 0, 1, 1)];
+        let rates = rates(&data, CONFIG);
+
+        assert_eq!(rates.case_fatality, vec![None, None]);
+        assert_eq!(rates.hospitalisation, vec![None, None]);
+        assert_eq!(rates.lagged_case_fatality, vec![None, None]);
+    }
+}
@@ -0,0 +1,215 @@
+use crate::data::{Data, DataPoint};
+use chrono::{prelude::*, Duration};
+use clap::ValueEnum;
+use tracing::instrument;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum Resolution {
+    /// One point per day, as reported
+    #[default]
+    Daily,
+    /// One point per ISO week, starting on Monday
+    Weekly,
+    /// One point per calendar month
+    Monthly,
+}
+
+impl Resolution {
+    fn period_start(self, date: Date<Utc>) -> Date<Utc> {
+        match self {
+            Resolution::Daily => date,
+            Resolution::Weekly => {
+                date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+            }
+            Resolution::Monthly => Utc.ymd(date.year(), date.month(), 1),
+        }
+    }
+}
+
+/// Aggregates the data points into one point per period.
+///
+/// Increases and reported cases are summed up, totals are taken from the
+/// last day of the period, and the incidences are averaged.
+/// The date of an aggregated point is the first day of its period.
+#[instrument(skip(data_points))]
+pub fn resample(data_points: &[DataPoint], resolution: Resolution) -> Data {
+    if resolution == Resolution::Daily {
+        return data_points.to_vec();
+    }
+
+    let mut resampled = Data::new();
+    let mut start = 0;
+    while start < data_points.len() {
+        let period = resolution.period_start(data_points[start].dates.date);
+        let len = data_points[start..]
+            .iter()
+            .take_while(|d| resolution.period_start(d.dates.date) == period)
+            .count();
+        resampled.push(aggregate(period, &data_points[start..start + len]));
+        start += len;
+    }
+    resampled
+}
+
+/// Days between the last two data points, 1 for daily data
+pub fn step_days(data_points: &[DataPoint]) -> i64 {
+    data_points
+        .windows(2)
+        .last()
+        .map(|win| (win[1].dates.date - win[0].dates.date).num_days())
+        .unwrap_or(1)
+        .max(1)
+}
+
+fn aggregate(period: Date<Utc>, period_points: &[DataPoint]) -> DataPoint {
+    let first = &period_points[0];
+    let last = &period_points[period_points.len() - 1];
+    let days = period_points.len() as f64;
+
+    let mut data_point = last.clone();
+    data_point.dates.date = period;
+    data_point.dates.date_ts = period.and_hms(12, 0, 0);
+    data_point.dates.date_range = format!(
+        "{} - {}",
+        first.dates.date.format("%d.%m.%Y"),
+        last.dates.date.format("%d.%m.%Y")
+    );
+    data_point.show = period_points.iter().any(|d| d.show);
+    data_point.incidence = period_points.iter().map(|d| d.incidence).sum::<f64>() / days;
+    data_point.incidence_calculated = period_points
+        .iter()
+        .map(|d| d.incidence_calculated)
+        .sum::<f64>()
+        / days;
+    data_point.cases.reported = period_points.iter().map(|d| d.cases.reported).sum();
+
+    macro_rules! sum_increase {
+        ($($value:ident),+) => {{
+            $(
+                data_point.$value.increase = period_points.iter().map(|d| d.$value.increase).sum();
+            )+
+        }};
+    }
+
+    sum_increase!(cases, deaths, recoveries, hospitalisations);
+
+    data_point
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    /// Consecutive days, the n-th day has n new cases and an incidence of n
+    fn days(first: Date<Utc>, count: u32) -> Data {
+        (1..=count)
+            .map(|n| {
+                let mut data_point = fixtures::on(first + Duration::days(i64::from(n) - 1));
+                data_point.incidence = f64::from(n);
+                data_point.incidence_calculated = f64::from(2 * n);
+                data_point.cases.increase = n as i32;
+                data_point.cases.total = n * (n + 1) / 2;
+                data_point.cases.reported = 10;
+                data_point.deaths.increase = 1;
+                data_point.deaths.total = n;
+                data_point.hospitalisations.beds_in_use = 100 + n;
+                data_point
+            })
+            .collect()
+    }
+
+    fn dates(data: &[DataPoint]) -> Vec<Date<Utc>> {
+        data.iter().map(|d| d.dates.date).collect()
+    }
+
+    #[test]
+    fn daily_data_is_unchanged() {
+        let data = days(Utc.ymd(2021, 11, 1), 5);
+
+        assert_eq!(dates(&resample(&data, Resolution::Daily)), dates(&data));
+    }
+
+    #[test]
+    fn iso_weeks_across_the_year_boundary() {
+        // Thursday, 31 December 2020 is in the ISO week starting on Monday, 28 December
+        let weekly = resample(&days(Utc.ymd(2020, 12, 31), 11), Resolution::Weekly);
+
+        assert_eq!(
+            dates(&weekly),
+            vec![Utc.ymd(2020, 12, 28), Utc.ymd(2021, 1, 4)]
+        );
+        assert_eq!(weekly[0].dates.date_range, "31.12.2020 - 03.01.2021");
+        assert_eq!(weekly[1].dates.date_range, "04.01.2021 - 10.01.2021");
+        assert_eq!(
+            weekly[0].dates.date_ts,
+            Utc.ymd(2020, 12, 28).and_hms(12, 0, 0)
+        );
+    }
+
+    #[test]
+    fn calendar_months() {
+        let monthly = resample(&days(Utc.ymd(2021, 11, 29), 35), Resolution::Monthly);
+
+        assert_eq!(
+            dates(&monthly),
+            vec![
+                Utc.ymd(2021, 11, 1),
+                Utc.ymd(2021, 12, 1),
+                Utc.ymd(2022, 1, 1)
+            ]
+        );
+        assert_eq!(
+            monthly
+                .iter()
+                .map(|d| d.dates.date_range.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "29.11.2021 - 30.11.2021",
+                "01.12.2021 - 31.12.2021",
+                "01.01.2022 - 02.01.2022"
+            ]
+        );
+    }
+
+    #[test]
+    fn increases_and_reported_cases_are_summed() {
+        let weekly = resample(&days(Utc.ymd(2021, 11, 1), 7), Resolution::Weekly);
+
+        assert_eq!(weekly.len(), 1);
+        assert_eq!(weekly[0].cases.increase, 1 + 2 + 3 + 4 + 5 + 6 + 7);
+        assert_eq!(weekly[0].cases.reported, 70);
+        assert_eq!(weekly[0].deaths.increase, 7);
+    }
+
+    #[test]
+    fn totals_are_taken_from_the_last_day() {
+        let weekly = resample(&days(Utc.ymd(2021, 11, 1), 7), Resolution::Weekly);
+
+        assert_eq!(weekly[0].cases.total, 28);
+        assert_eq!(weekly[0].deaths.total, 7);
+        assert_eq!(weekly[0].hospitalisations.beds_in_use, 107);
+    }
+
+    #[test]
+    fn incidences_are_averaged() {
+        let weekly = resample(&days(Utc.ymd(2021, 11, 1), 7), Resolution::Weekly);
+
+        assert_eq!(weekly[0].incidence, 4.0);
+        assert_eq!(weekly[0].incidence_calculated, 8.0);
+    }
+
+    #[test]
+    fn partial_last_period() {
+        let weekly = resample(&days(Utc.ymd(2021, 11, 1), 10), Resolution::Weekly);
+        let last = &weekly[1];
+
+        assert_eq!(weekly.len(), 2);
+        assert_eq!(last.dates.date, Utc.ymd(2021, 11, 8));
+        assert_eq!(last.dates.date_range, "08.11.2021 - 10.11.2021");
+        assert_eq!(last.cases.increase, 8 + 9 + 10);
+        assert_eq!(last.cases.total, 55);
+        assert_eq!(last.incidence, 9.0);
+        assert_eq!(step_days(&weekly), 7);
+    }
+}
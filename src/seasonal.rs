@@ -0,0 +1,230 @@
+use crate::{data::DataPoint, resample};
+use chrono::Datelike;
+use tracing::{debug, instrument};
+
+/// Multiplicative decomposition of the daily reported cases into
+/// `reported = trend * weekday * residual`.
+#[derive(Debug, Clone)]
+pub struct Decomposition {
+    /// Centered 7-day moving average, not available for the first and last 3 days
+    pub trend: Vec<Option<f64>>,
+    /// Weekday factor of each data point
+    pub weekday: Vec<f64>,
+    /// Whatever is not explained by trend and weekday
+    pub residual: Vec<Option<f64>>,
+    /// Reported cases with the weekday effect removed
+    pub adjusted: Vec<f64>,
+}
+
+/// Only daily data has a weekday effect, the decomposition of weekly or
+/// monthly data would be meaningless and is `None`. So is the decomposition
+/// of less than a week of data, which has no trend.
+#[instrument(skip(data_points))]
+pub fn decompose(data_points: &[DataPoint]) -> Option<Decomposition> {
+    if resample::step_days(data_points) != 1 {
+        debug!("Not decomposing data that is not daily");
+        return None;
+    }
+    if data_points.len() < 7 {
+        debug!("Not decomposing less than a week of data");
+        return None;
+    }
+
+    let reported = data_points
+        .iter()
+        .map(|d| f64::from(d.cases.reported))
+        .collect::<Vec<_>>();
+
+    let trend = (0..reported.len())
+        .map(|i| {
+            let from = i.checked_sub(3)?;
+            let window = reported.get(from..i + 4)?;
+            Some(window.iter().sum::<f64>() / 7.0)
+        })
+        .collect::<Vec<_>>();
+
+    let mut ratio_sums = [0.0; 7];
+    let mut ratio_counts = [0_u32; 7];
+    for ((d, value), trend) in data_points.iter().zip(&reported).zip(&trend) {
+        if let Some(trend) = trend.filter(|t| *t > 0.0) {
+            let weekday = weekday_index(d);
+            ratio_sums[weekday] += value / trend;
+            ratio_counts[weekday] += 1;
+        }
+    }
+
+    let mut weekday_factors = [1.0; 7];
+    for (factor, (sum, count)) in weekday_factors
+        .iter_mut()
+        .zip(ratio_sums.iter().zip(&ratio_counts))
+    {
+        if *count > 0 {
+            *factor = sum / f64::from(*count);
+        }
+    }
+    let mean_factor = weekday_factors.iter().sum::<f64>() / 7.0;
+    if mean_factor > 0.0 {
+        weekday_factors.iter_mut().for_each(|f| *f /= mean_factor);
+    }
+    debug!("Weekday factors: {:?}", weekday_factors);
+
+    let weekday = data_points
+        .iter()
+        .map(|d| weekday_factors[weekday_index(d)])
+        .collect::<Vec<_>>();
+
+    let residual = reported
+        .iter()
+        .zip(&trend)
+        .zip(&weekday)
+        .map(|((value, trend), weekday)| {
+            trend
+                .filter(|t| *t > 0.0 && *weekday > 0.0)
+                .map(|t| value / (t * weekday))
+        })
+        .collect();
+
+    let adjusted = reported
+        .iter()
+        .zip(&weekday)
+        .map(|(value, weekday)| {
+            if *weekday > 0.0 {
+                value / weekday
+            } else {
+                *value
+            }
+        })
+        .collect();
+
+    Some(Decomposition {
+        trend,
+        weekday,
+        residual,
+        adjusted,
+    })
+}
+
+/// The incidence that the weekday adjusted cases of the previous day would
+/// amount to if they were reported on every day of the week.
+///
+/// This aligns with `incidence_calculated`, which also only counts the days
+/// before the data point.
+pub fn adjusted_incidence(data_points: &[DataPoint], decomposition: &Decomposition) -> Vec<f64> {
+    let per_case = incidence_per_case(data_points);
+    (0..data_points.len())
+        .map(|i| match i.checked_sub(1) {
+            Some(prev) => decomposition.adjusted[prev] * 7.0 * per_case,
+            None => 0.0,
+        })
+        .collect()
+}
+
+/// Recovers the `100_000 / population` factor that was used to calculate the incidence
+fn incidence_per_case(data_points: &[DataPoint]) -> f64 {
+    (7..data_points.len())
+        .rev()
+        .find_map(|i| {
+            let cases = data_points[i - 7..i]
+                .iter()
+                .map(|d| u64::from(d.cases.reported))
+                .sum::<u64>();
+            if cases > 0 && data_points[i].incidence_calculated > 0.0 {
+                Some(data_points[i].incidence_calculated / cases as f64)
+            } else {
+                None
+            }
+        })
+        .unwrap_or_default()
+}
+
+fn weekday_index(data_point: &DataPoint) -> usize {
+    data_point.dates.date.weekday().num_days_from_monday() as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::{Duration, TimeZone, Utc};
+
+    /// Reported cases twice as high on Mondays and half as high on Sundays
+    const PATTERN: [f64; 7] = [2.0, 1.0, 1.0, 1.0, 1.0, 1.0, 0.5];
+
+    /// Daily data starting on Monday, 1 November 2021
+    fn daily(reported: impl IntoIterator<Item = u32>) -> Vec<DataPoint> {
+        reported
+            .into_iter()
+            .enumerate()
+            .map(|(i, reported)| {
+                let mut data_point = fixtures::on(Utc.ymd(2021, 11, 1) + Duration::days(i as i64));
+                data_point.cases.reported = reported;
+                data_point
+            })
+            .collect()
+    }
+
+    fn weeks_with_pattern(weeks: usize) -> Vec<DataPoint> {
+        daily(
+            PATTERN
+                .iter()
+                .cycle()
+                .take(7 * weeks)
+                .map(|factor| (factor * 1000.0) as u32),
+        )
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn weekday_factors_average_to_one() {
+        let decomposition = decompose(&daily((0..30).map(|i| 100 + i * i % 37))).unwrap();
+
+        let mean = decomposition.weekday[..7].iter().sum::<f64>() / 7.0;
+        assert_close(mean, 1.0);
+    }
+
+    #[test]
+    fn weekday_pattern_is_recovered() {
+        let decomposition = decompose(&weeks_with_pattern(4)).unwrap();
+        let mean = PATTERN.iter().sum::<f64>() / 7.0;
+
+        for (factor, expected) in decomposition.weekday.iter().zip(PATTERN.iter().cycle()) {
+            assert_close(*factor, expected / mean);
+        }
+        for adjusted in &decomposition.adjusted {
+            assert_close(*adjusted, 1000.0 * mean);
+        }
+        for residual in decomposition.residual.iter().flatten() {
+            assert_close(*residual, 1.0);
+        }
+    }
+
+    #[test]
+    fn trend_is_not_available_at_the_ends() {
+        let decomposition = decompose(&weeks_with_pattern(2)).unwrap();
+
+        assert!(decomposition.trend[..3].iter().all(Option::is_none));
+        assert!(decomposition.trend[3..11].iter().all(Option::is_some));
+        assert!(decomposition.trend[11..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn weekly_data_is_not_decomposed() {
+        let weekly = resample::resample(&weeks_with_pattern(4), resample::Resolution::Weekly);
+
+        assert!(decompose(&weekly).is_none());
+    }
+
+    #[test]
+    fn less_than_a_week_is_not_decomposed() {
+        assert!(decompose(&[]).is_none());
+        assert!(decompose(&daily([1, 2, 3, 4, 5, 6])).is_none());
+    }
+}
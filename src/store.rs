@@ -0,0 +1,247 @@
+//! An SQLite database next to the cache that keeps every fetched snapshot of
+//! the data, so that the history can be queried with SQL.
+
+use crate::{cache, data::DataPoint, output::Record, Result};
+use chrono::{DateTime, Duration, Utc};
+use color_eyre::{eyre::eyre, Help};
+use rusqlite::{params, types::ValueRef, Connection, OpenFlags};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::{debug, instrument, trace, warn};
+
+static STORE_FILE: &str = "history.sqlite";
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS snapshots (
+    id INTEGER PRIMARY KEY,
+    fetched_at TEXT NOT NULL,
+    population INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS data_points (
+    snapshot_id INTEGER NOT NULL REFERENCES snapshots (id) ON DELETE CASCADE,
+    date TEXT NOT NULL,
+    incidence REAL NOT NULL,
+    incidence_reported REAL NOT NULL,
+    cases INTEGER NOT NULL,
+    cases_increase INTEGER NOT NULL,
+    cases_reported INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    deaths_increase INTEGER NOT NULL,
+    hospitalisations INTEGER NOT NULL,
+    hospitalisations_increase INTEGER NOT NULL,
+    beds_in_use INTEGER NOT NULL,
+    recoveries INTEGER NOT NULL,
+    recoveries_increase INTEGER NOT NULL,
+    PRIMARY KEY (snapshot_id, date)
+);
+CREATE VIEW IF NOT EXISTS latest AS
+    SELECT * FROM data_points WHERE snapshot_id = (SELECT max(id) FROM snapshots);
+";
+
+pub fn store_file() -> Option<PathBuf> {
+    cache::cache_dir().map(|dir| dir.join(STORE_FILE))
+}
+
+/// Adds the data as a new snapshot to the store.
+///
+/// Like the cache, the store is not required to show the data, so failures are only logged.
+#[instrument(skip(data))]
+pub fn record_snapshot(data: &[DataPoint], population: u32, fetched_at: DateTime<Utc>) {
+    let file = match store_file() {
+        None => return,
+        Some(file) => file,
+    };
+    trace!("store file {}", file.display());
+
+    if let Err(e) = write_snapshot(&file, data, population, fetched_at) {
+        warn!("Could not write the snapshot to the store at [{}]. While this is not an error, it is recommended to investigate the reason, as the history could otherwise not be queried. Error: [{}].", file.display(), e);
+    }
+}
+
+fn write_snapshot(
+    file: &Path,
+    data: &[DataPoint],
+    population: u32,
+    fetched_at: DateTime<Utc>,
+) -> Result<()> {
+    if let Some(parent) = file.parent() {
+        if !parent.exists() {
+            std::fs::create_dir_all(parent)?;
+        }
+    }
+    let mut conn = open(file)?;
+    insert_snapshot(&mut conn, data, population, fetched_at)
+}
+
+/// Opens the store for writing, SQLite only deletes the data points of a
+/// snapshot if the foreign keys are enabled for every connection
+fn open(file: &Path) -> Result<Connection> {
+    let conn = Connection::open(file)?;
+    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    conn.execute_batch(SCHEMA)?;
+    Ok(conn)
+}
+
+fn insert_snapshot(
+    conn: &mut Connection,
+    data: &[DataPoint],
+    population: u32,
+    fetched_at: DateTime<Utc>,
+) -> Result<()> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "INSERT INTO snapshots (fetched_at, population) VALUES (?1, ?2)",
+        params![fetched_at.to_rfc3339(), population],
+    )?;
+    let snapshot = tx.last_insert_rowid();
+    {
+        let mut insert = tx.prepare(
+            "INSERT INTO data_points VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        )?;
+        for d in data {
+            insert.execute(params![
+                snapshot,
+                d.dates.date.format("%Y-%m-%d").to_string(),
+                d.incidence_calculated,
+                d.incidence,
+                d.cases.total,
+                d.cases.increase,
+                d.cases.reported,
+                d.deaths.total,
+                d.deaths.increase,
+                d.hospitalisations.total,
+                d.hospitalisations.increase,
+                d.hospitalisations.beds_in_use,
+                d.recoveries.total,
+                d.recoveries.increase,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    debug!("Stored {} data points as snapshot {}", data.len(), snapshot);
+
+    Ok(())
+}
+
+/// Removes the snapshots like [`cache::prune_snapshots`] removes the snapshot files,
+/// returns how many were removed
+#[instrument(err)]
+pub fn prune(keep: Option<usize>, older_than: Option<Duration>) -> Result<usize> {
+    let file = match store_file().filter(|file| file.exists()) {
+        None => return Ok(0),
+        Some(file) => file,
+    };
+    trace!("store file {}", file.display());
+
+    let pruned = prune_snapshots(&open(&file)?, keep, older_than)?;
+    debug!("Pruned {} snapshots from the store", pruned);
+    Ok(pruned)
+}
+
+fn prune_snapshots(
+    conn: &Connection,
+    keep: Option<usize>,
+    older_than: Option<Duration>,
+) -> Result<usize> {
+    let keep = keep.unwrap_or(0) as i64;
+    let before = older_than.map(|age| (Utc::now() - age).to_rfc3339());
+    let pruned = conn.execute(
+        "DELETE FROM snapshots
+        WHERE id NOT IN (
+            SELECT id FROM snapshots ORDER BY julianday(fetched_at) DESC, id DESC LIMIT ?1
+        )
+        AND (?2 IS NULL OR julianday(fetched_at) < julianday(?2))",
+        params![keep, before],
+    )?;
+    Ok(pruned)
+}
+
+/// Runs a read-only query against the store
+#[instrument(err)]
+pub fn query(sql: &str) -> Result<Vec<Record>> {
+    let file = store_file().filter(|file| file.exists()).ok_or_else(|| {
+        eyre!("There is no history stored yet")
+            .suggestion("Run the `cache refresh` subcommand to store a first snapshot.")
+    })?;
+    trace!("store file {}", file.display());
+
+    let conn = Connection::open_with_flags(&file, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut statement = conn.prepare(sql)?;
+    let names = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+    let mut rows = statement.query([])?;
+    let mut records = Vec::new();
+    while let Some(row) = rows.next()? {
+        let mut record = Record::default();
+        for (i, name) in names.iter().enumerate() {
+            let value = match row.get_ref(i)? {
+                ValueRef::Null => Value::Null,
+                ValueRef::Integer(i) => Value::from(i),
+                ValueRef::Real(f) => Value::from(f),
+                ValueRef::Text(t) => Value::from(String::from_utf8_lossy(t).into_owned()),
+                ValueRef::Blob(b) => {
+                    Value::from(b.iter().map(|b| format!("{:02x}", b)).collect::<String>())
+                }
+            };
+            record.push(name.as_str(), value);
+        }
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::TimeZone;
+
+    fn store_with_snapshots(days: &[u32]) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = ON;").unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        for &day in days {
+            let fetched_at = Utc.ymd(2021, 11, day).and_hms(8, 0, 0);
+            insert_snapshot(&mut conn, &[fixtures::day(day)], 500_000, fetched_at).unwrap();
+        }
+        conn
+    }
+
+    fn count(conn: &Connection, table: &str) -> i64 {
+        conn.query_row(&format!("SELECT count(*) FROM {}", table), [], |row| {
+            row.get(0)
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn pruning_removes_the_data_points_of_the_snapshots() {
+        let conn = store_with_snapshots(&[1, 2, 3]);
+
+        let pruned = prune_snapshots(&conn, Some(1), None).unwrap();
+
+        assert_eq!(pruned, 2);
+        assert_eq!(count(&conn, "snapshots"), 1);
+        assert_eq!(count(&conn, "data_points"), 1);
+        let date: String = conn
+            .query_row("SELECT date FROM latest", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(date, "2021-11-03");
+    }
+
+    #[test]
+    fn pruning_keeps_newer_snapshots() {
+        let conn = store_with_snapshots(&[1, 2, 3]);
+        let age = Utc::now() - Utc.ymd(2021, 11, 2).and_hms(12, 0, 0);
+
+        let pruned = prune_snapshots(&conn, None, Some(age)).unwrap();
+
+        assert_eq!(pruned, 2);
+        assert_eq!(count(&conn, "data_points"), 1);
+    }
+}
@@ -0,0 +1,312 @@
+use crate::{
+    data::DataPoint,
+    history::{self, Context},
+    output::{Record, Text},
+    projection::Estimate,
+    rates, seasonal,
+};
+use chrono::{Date, Duration, Utc};
+use std::fmt::Display;
+use tracing::instrument;
+
+/// Summarizes the latest data point and compares it to an earlier one.
+///
+/// Without a `compare` duration, the latest point is compared to its
+/// predecessor, otherwise to the latest point that is at least that much older.
+#[instrument(skip(data_points))]
+pub fn summarized_data(
+    data_points: &[DataPoint],
+    compare: Option<Duration>,
+    rates: rates::Config,
+) -> SummarizedData {
+    let mut data = SummarizedData::default();
+    let (latest, previous) = match data_points.split_last() {
+        Some(split) => split,
+        None => return data,
+    };
+
+    data.date = Some(latest.dates.date);
+    data.incidence = latest.incidence_calculated;
+    data.cases = latest.cases.total;
+    data.deaths = latest.deaths.total;
+    data.hospitalisations = latest.hospitalisations.total;
+    data.recoveries = latest.recoveries.total;
+
+    let compared = match compare {
+        Some(compare) => {
+            let date = latest.dates.date - compare;
+            previous.iter().rev().find(|d| d.dates.date <= date)
+        }
+        None => previous.last(),
+    };
+    if let Some(dp) = compared {
+        data.compared_to = Some(dp.dates.date);
+        data.incidence_increase = data.incidence - dp.incidence_calculated;
+        data.incidence_increase_percent = percent(data.incidence_increase, dp.incidence_calculated);
+
+        macro_rules! deltas {
+            ($($value:ident),+) => {{
+                $(
+                    let (increase, percent) = delta(data.$value, dp.$value.total);
+                    data.increases.$value = increase;
+                    data.increases_percent.$value = percent;
+                )+
+            }};
+        }
+
+        deltas!(cases, deaths, hospitalisations, recoveries);
+    }
+
+    if let Some(decomposition) = seasonal::decompose(data_points) {
+        data.incidence_weekday_adjusted = seasonal::adjusted_incidence(data_points, &decomposition)
+            .last()
+            .copied();
+    }
+    let (case_fatality, hospitalisation, lagged_case_fatality) =
+        rates::rates(data_points, rates).last();
+    data.case_fatality_rate = case_fatality;
+    data.hospitalisation_rate = hospitalisation;
+    data.lagged_case_fatality_rate = lagged_case_fatality;
+    data.history = history::contexts(data_points);
+
+    data
+}
+
+fn delta(current: u32, previous: u32) -> (i64, Option<f64>) {
+    let increase = i64::from(current) - i64::from(previous);
+    (increase, percent(increase as f64, f64::from(previous)))
+}
+
+fn percent(increase: f64, previous: f64) -> Option<f64> {
+    if previous == 0.0 {
+        None
+    } else {
+        Some(increase * 100.0 / previous)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SummarizedData {
+    pub date: Option<Date<Utc>>,
+    pub compared_to: Option<Date<Utc>>,
+    pub recoveries: u32,
+    pub hospitalisations: u32,
+    pub deaths: u32,
+    pub cases: u32,
+    pub increases: Increases<i64>,
+    pub increases_percent: Increases<Option<f64>>,
+    pub incidence: f64,
+    pub incidence_increase: f64,
+    pub incidence_increase_percent: Option<f64>,
+    /// Only available for daily data
+    pub incidence_weekday_adjusted: Option<f64>,
+    /// The incidence corrected for the expected revisions of the latest days
+    pub incidence_nowcast: Option<Estimate>,
+    pub case_fatality_rate: Option<f64>,
+    pub hospitalisation_rate: Option<f64>,
+    pub lagged_case_fatality_rate: Option<f64>,
+    pub history: Vec<Context>,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Increases<T> {
+    pub recoveries: T,
+    pub hospitalisations: T,
+    pub deaths: T,
+    pub cases: T,
+}
+
+impl SummarizedData {
+    /// The summary as a flat record, this is the stable schema of the machine-readable output
+    pub fn record(&self) -> Record {
+        let mut record = Record::default();
+        record.push("date", self.date.map(format_date));
+        record.push("compared_to", self.compared_to.map(format_date));
+        record.push("incidence", self.incidence);
+        record.push("incidence_increase", self.incidence_increase);
+        record.push(
+            "incidence_increase_percent",
+            self.incidence_increase_percent,
+        );
+        record.push(
+            "incidence_weekday_adjusted",
+            self.incidence_weekday_adjusted,
+        );
+        record.push("incidence_nowcast", self.incidence_nowcast.map(|e| e.value));
+        record.push(
+            "incidence_nowcast_lower",
+            self.incidence_nowcast.map(|e| e.lower),
+        );
+        record.push(
+            "incidence_nowcast_upper",
+            self.incidence_nowcast.map(|e| e.upper),
+        );
+
+        macro_rules! counts {
+            ($($value:ident),+) => {{
+                $(
+                    record.push(stringify!($value), self.$value);
+                    record.push(
+                        concat!(stringify!($value), "_increase"),
+                        self.increases.$value,
+                    );
+                    record.push(
+                        concat!(stringify!($value), "_increase_percent"),
+                        self.increases_percent.$value,
+                    );
+                )+
+            }};
+        }
+
+        counts!(cases, deaths, hospitalisations, recoveries);
+
+        record.push("case_fatality_rate", self.case_fatality_rate);
+        record.push("hospitalisation_rate", self.hospitalisation_rate);
+        record.push("lagged_case_fatality_rate", self.lagged_case_fatality_rate);
+        for context in &self.history {
+            let metric = context.metric.name();
+            record.push(format!("{}_percentile", metric), context.percentile);
+            record.push(format!("{}_max", metric), context.max);
+            record.push(
+                format!("{}_max_date", metric),
+                format_date(context.max_date),
+            );
+            record.push(
+                format!("{}_highest_since", metric),
+                context.highest_since.map(format_date),
+            );
+            record.push(
+                format!("{}_lowest_since", metric),
+                context.lowest_since.map(format_date),
+            );
+        }
+        record
+    }
+}
+
+impl Display for SummarizedData {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (key, value) in self.record().iter() {
+            writeln!(f, "{}: {}", key, Text(value))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn format_date(date: Date<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+pub struct OptionalValue<T>(pub Option<T>);
+
+impl<T: Display> Display for OptionalValue<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(value) => value.fmt(f),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+    use chrono::TimeZone;
+
+    fn data_point(day: u32, cases: u32, deaths: u32, incidence: f64) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.total = cases;
+        data_point.deaths.total = deaths;
+        data_point.incidence_calculated = incidence;
+        data_point
+    }
+
+    #[test]
+    fn decreasing_totals_have_negative_increases() {
+        let data = [data_point(1, 1000, 10, 200.0), data_point(2, 990, 8, 150.0)];
+        let summary = summarized_data(&data, None, rates::Config::default());
+
+        assert_eq!(summary.cases, 990);
+        assert_eq!(summary.increases.cases, -10);
+        assert_eq!(summary.increases.deaths, -2);
+        assert_eq!(summary.increases_percent.cases, Some(-1.0));
+        assert_eq!(summary.increases_percent.deaths, Some(-20.0));
+        assert_eq!(summary.incidence_increase, -50.0);
+        assert_eq!(summary.incidence_increase_percent, Some(-25.0));
+    }
+
+    #[test]
+    fn increasing_totals_have_positive_increases() {
+        let data = [
+            data_point(1, 1000, 10, 100.0),
+            data_point(2, 1100, 11, 150.0),
+        ];
+        let summary = summarized_data(&data, None, rates::Config::default());
+
+        assert_eq!(summary.increases.cases, 100);
+        assert_eq!(summary.increases.deaths, 1);
+        assert_eq!(summary.increases_percent.cases, Some(10.0));
+        assert_eq!(summary.increases_percent.deaths, Some(10.0));
+        assert_eq!(summary.incidence_increase_percent, Some(50.0));
+    }
+
+    #[test]
+    fn no_percentage_when_comparing_to_zero() {
+        let data = [data_point(1, 0, 0, 0.0), data_point(2, 10, 0, 5.0)];
+        let summary = summarized_data(&data, None, rates::Config::default());
+
+        assert_eq!(summary.increases.cases, 10);
+        assert_eq!(summary.increases_percent.cases, None);
+        assert_eq!(summary.increases_percent.deaths, None);
+        assert_eq!(summary.incidence_increase_percent, None);
+    }
+
+    #[test]
+    fn compares_to_the_point_at_least_the_duration_before() {
+        let data = (1..=10)
+            .map(|day| data_point(day, 1000 - day, 0, 0.0))
+            .collect::<Vec<_>>();
+        let summary = summarized_data(&data, Some(Duration::days(7)), rates::Config::default());
+
+        assert_eq!(summary.date, Some(Utc.ymd(2021, 11, 10)));
+        assert_eq!(summary.compared_to, Some(Utc.ymd(2021, 11, 3)));
+        assert_eq!(summary.increases.cases, -7);
+    }
+
+    #[test]
+    fn compares_to_nothing_if_the_duration_is_too_long() {
+        let data = [data_point(1, 1000, 10, 200.0), data_point(2, 990, 8, 150.0)];
+        let summary = summarized_data(&data, Some(Duration::days(7)), rates::Config::default());
+
+        assert_eq!(summary.compared_to, None);
+        assert_eq!(summary.increases, Increases::default());
+    }
+
+    #[test]
+    fn weekday_adjustment_only_for_daily_data() {
+        let daily = (1..=15)
+            .map(|day| data_point(day, day * 100, 0, 50.0))
+            .collect::<Vec<_>>();
+        let weekly = [1, 8, 15]
+            .iter()
+            .map(|&day| data_point(day, day * 100, 0, 50.0))
+            .collect::<Vec<_>>();
+
+        let daily = summarized_data(&daily, None, rates::Config::default());
+        let weekly = summarized_data(&weekly, None, rates::Config::default());
+
+        assert!(daily.incidence_weekday_adjusted.is_some());
+        assert_eq!(weekly.incidence_weekday_adjusted, None);
+    }
+
+    #[test]
+    fn single_data_point_has_no_increases() {
+        let data = [data_point(1, 1000, 10, 200.0)];
+        let summary = summarized_data(&data, None, rates::Config::default());
+
+        assert_eq!(summary.cases, 1000);
+        assert_eq!(summary.compared_to, None);
+        assert_eq!(summary.increases, Increases::default());
+    }
+}
@@ -1,8 +1,17 @@
 use crate::{
-    data::DataPoint,
+    correlation::{self, CrossCorrelation, Series},
+    data::{Data, DataPoint},
+    history::{self, Context},
     messages::{Messages, MsgId},
+    nowcast::NowcastPoint,
+    projection::{self, ProjectedPoint, Projection},
+    rates::{self, Rates},
+    seasonal::{self, Decomposition},
+    waves::{self, Wave},
     Result,
 };
+use chrono::{Date, DateTime, Datelike, NaiveDate, TimeZone, Utc};
+use color_eyre::eyre::eyre;
 use crossterm::{
     event::{
         self, DisableMouseCapture, EnableMouseCapture, Event as CEvent, KeyCode, KeyEvent,
@@ -11,22 +20,81 @@ use crossterm::{
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use std::io::stdout;
+use std::{
+    io::stdout,
+    sync::mpsc::{Receiver, TryRecvError},
+    time::Duration,
+};
 use tracing::{debug, instrument, trace};
 use tui::{
     backend::CrosstermBackend,
-    layout::Constraint,
     layout::Rect,
+    layout::{Constraint, Direction, Layout},
     style::Modifier,
     style::{Color, Style},
     symbols,
-    text::Span,
-    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType},
+    text::{Span, Spans},
+    widgets::{Axis, Block, Borders, Chart, Dataset, GraphType, Paragraph},
     Frame, Terminal,
 };
 
+#[derive(Debug)]
+pub struct Options {
+    pub projection: Option<projection::Config>,
+    pub rates: rates::Config,
+    pub wave_prominence: f64,
+    pub max_lag: usize,
+    /// Start with the lag-shifted series instead of the totals
+    pub show_lags: bool,
+    /// The requested date and the fetch time of the snapshot that is shown instead of the current data
+    pub as_of: Option<(NaiveDate, DateTime<Utc>)>,
+    /// Corrections of the most recent data points for their expected revisions
+    pub nowcast: Vec<NowcastPoint>,
+    /// A download of new data that replaces the shown data once it finished
+    pub refresh: Option<Refresh>,
+}
+
+/// How often the pending refresh is checked while waiting for input
+const REFRESH_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug)]
+pub struct Refresh {
+    /// When the data that is shown until the refresh finished was fetched
+    pub fetched_at: DateTime<Utc>,
+    pub receiver: Receiver<Result<Refreshed>>,
+}
+
+/// The new data, prepared like the data that it replaces
+pub struct Refreshed {
+    pub data_points: Data,
+    pub nowcast: Vec<NowcastPoint>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            projection: None,
+            rates: rates::Config::default(),
+            wave_prominence: waves::DEFAULT_PROMINENCE,
+            max_lag: correlation::DEFAULT_MAX_LAG,
+            show_lags: false,
+            as_of: None,
+            nowcast: Vec::new(),
+            refresh: None,
+        }
+    }
+}
+
 #[instrument(err, skip(data_points, msg))]
-pub fn draw(data_points: &[DataPoint], msg: Messages) -> Result<()> {
+pub fn draw(mut data_points: Data, msg: Messages, mut options: Options) -> Result<()> {
+    let nowcast = std::mem::take(&mut options.nowcast);
+    let mut derived = Derived::new(&data_points, &options, nowcast);
+    let mut refresh = options.refresh.take();
+    let mut status = options
+        .as_of
+        .map(|(date, fetched_at)| msg.as_of(date, fetched_at))
+        .or_else(|| refresh.as_ref().map(|r| msg.refreshing(r.fetched_at)));
+
     enable_raw_mode()?;
     let mut stdout = stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -35,12 +103,43 @@ pub fn draw(data_points: &[DataPoint], msg: Messages) -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
-    let mut index = 0;
+    let mut state = State {
+        index: 0,
+        view: if options.show_lags {
+            View::Lags
+        } else {
+            View::Totals
+        },
+        info: false,
+    };
     loop {
-        debug!("Drawing charts with range {:?}", index..);
-        terminal.draw(|f| draw_charts(f, data_points.get(index..).unwrap_or_default(), &msg))?;
+        debug!("Drawing charts with {:?}", state);
+        terminal
+            .draw(|f| draw_charts(f, &data_points, state, &derived, status.as_deref(), &msg))?;
 
         let event = loop {
+            if let Some(result) = poll_refresh(&refresh) {
+                let fetched_at = refresh.take().map(|r| r.fetched_at);
+                match result {
+                    Ok(refreshed) => {
+                        debug!("Showing the refreshed data");
+                        data_points = refreshed.data_points;
+                        derived = Derived::new(&data_points, &options, refreshed.nowcast);
+                        state.index = state.index.min(data_points.len().saturating_sub(1));
+                        status = None;
+                    }
+                    Err(e) => {
+                        debug!("Could not refresh the data: {:?}", e);
+                        let reason = e.to_string();
+                        status =
+                            fetched_at.map(|fetched_at| msg.refresh_failed(fetched_at, &reason));
+                    }
+                }
+                break Event::Refreshed;
+            }
+            if refresh.is_some() && !event::poll(REFRESH_POLL)? {
+                continue;
+            }
             match event::read()? {
                 CEvent::Key(KeyEvent { code, .. }) => match code {
                     KeyCode::Char('q') => break Event::Quit,
@@ -62,6 +161,13 @@ pub fn draw(data_points: &[DataPoint], msg: Messages) -> Result<()> {
                     KeyCode::Char('8') => break Event::LastWeek(8),
                     KeyCode::Char('9') => break Event::LastWeek(9),
                     KeyCode::Esc | KeyCode::Home | KeyCode::Char('0') => break Event::AllData,
+                    KeyCode::Char('t') => break Event::Toggle(View::Trend),
+                    KeyCode::Char('r') => break Event::Toggle(View::Rates),
+                    KeyCode::Char('w') => break Event::Toggle(View::Waves(Alignment::Start)),
+                    KeyCode::Char('W') => break Event::Toggle(View::Waves(Alignment::Peak)),
+                    KeyCode::Char('y') => break Event::Toggle(View::Years),
+                    KeyCode::Char('c') => break Event::Toggle(View::Lags),
+                    KeyCode::Char('i') => break Event::ToggleInfo,
                     _ => {}
                 },
                 CEvent::Mouse(me) if matches!(me.kind, MouseEventKind::ScrollUp) => {
@@ -78,13 +184,23 @@ pub fn draw(data_points: &[DataPoint], msg: Messages) -> Result<()> {
         match event {
             Event::Quit => break,
             Event::ZoomIn(diff) => {
-                index = index
+                state.index = state
+                    .index
                     .saturating_add(diff)
                     .min(data_points.len().saturating_sub(1))
             }
-            Event::ZoomOut(diff) => index = index.saturating_sub(diff),
-            Event::LastWeek(week) => index = data_points.len().saturating_sub(7 * week),
-            Event::AllData => index = 0,
+            Event::ZoomOut(diff) => state.index = state.index.saturating_sub(diff),
+            Event::LastWeek(week) => state.index = data_points.len().saturating_sub(7 * week),
+            Event::AllData => state.index = 0,
+            Event::Toggle(toggled) => {
+                state.view = if state.view == toggled {
+                    View::Totals
+                } else {
+                    toggled
+                }
+            }
+            Event::ToggleInfo => state.info = !state.info,
+            Event::Refreshed => {}
         }
     }
 
@@ -99,29 +215,458 @@ pub fn draw(data_points: &[DataPoint], msg: Messages) -> Result<()> {
     Ok(())
 }
 
-fn draw_charts<B>(f: &mut Frame<'_, B>, data_points: &[DataPoint], msg: &Messages)
+/// The result of the refresh, once the download finished
+fn poll_refresh(refresh: &Option<Refresh>) -> Option<Result<Refreshed>> {
+    match refresh.as_ref()?.receiver.try_recv() {
+        Ok(result) => Some(result),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => Some(Err(eyre!("The download stopped unexpectedly"))),
+    }
+}
+
+/// Data that is derived from all data points, regardless of the zoom level
+#[derive(Debug)]
+struct Derived {
+    projection: Option<Projection>,
+    nowcast: Vec<NowcastPoint>,
+    decomposition: Option<Decomposition>,
+    rates: Rates,
+    waves: Vec<Wave>,
+    history: Vec<Context>,
+    correlations: Vec<CrossCorrelation>,
+}
+
+impl Derived {
+    fn new(data_points: &[DataPoint], options: &Options, nowcast: Vec<NowcastPoint>) -> Self {
+        Self {
+            projection: options
+                .projection
+                .and_then(|config| projection::project(data_points, config)),
+            nowcast,
+            decomposition: seasonal::decompose(data_points),
+            rates: rates::rates(data_points, options.rates),
+            waves: waves::detect(data_points, options.wave_prominence),
+            history: history::contexts(data_points),
+            correlations: correlation::cross_correlate(data_points, options.max_lag),
+        }
+    }
+}
+
+fn draw_charts<B>(
+    f: &mut Frame<'_, B>,
+    data_points: &[DataPoint],
+    state: State,
+    derived: &Derived,
+    status: Option<&str>,
+    msg: &Messages,
+) where
+    B: tui::backend::Backend,
+{
+    let State { index, view, info } = state;
+    let area = match status {
+        Some(status) => {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(0)])
+                .split(f.size());
+            draw_status(f, chunks[0], status);
+            chunks[1]
+        }
+        None => f.size(),
+    };
+    let (area, info_area) = if info {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(0),
+                Constraint::Length(derived.history.len() as u16 + 2),
+            ])
+            .split(area);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (area, None)
+    };
+
+    let visible = data_points.get(index..).unwrap_or_default();
+    match view {
+        View::Totals => {
+            let wave_peaks = derived
+                .waves
+                .iter()
+                .filter_map(|w| w.peak.checked_sub(index))
+                .collect::<Vec<_>>();
+            let data = chart_data(
+                area,
+                visible,
+                derived.projection.as_ref(),
+                &derived.nowcast,
+                &wave_peaks,
+            );
+            draw_chart_data(f, area, data, msg);
+        }
+        View::Trend => {
+            let trend = derived
+                .decomposition
+                .as_ref()
+                .and_then(|d| d.trend.get(index..))
+                .unwrap_or_default();
+            draw_trend(f, area, visible, trend, msg);
+        }
+        View::Rates => draw_rates(f, area, visible, &derived.rates, index, msg),
+        View::Waves(alignment) => draw_waves(f, area, data_points, &derived.waves, alignment, msg),
+        View::Years => draw_years(f, area, data_points, msg),
+        View::Lags => draw_lags(f, area, visible, &derived.correlations, msg),
+    }
+
+    if let Some(info_area) = info_area {
+        draw_info(f, info_area, &derived.history, msg);
+    }
+}
+
+fn draw_status<B>(f: &mut Frame<'_, B>, area: Rect, status: &str)
 where
     B: tui::backend::Backend,
 {
-    let data = chart_data(f.size(), data_points);
-    draw_chart_data(f, data, msg);
+    let style = Style::default()
+        .fg(Color::Black)
+        .bg(Color::Yellow)
+        .add_modifier(Modifier::BOLD);
+    let status = Paragraph::new(Span::styled(format!(" {}", status), style)).style(style);
+    f.render_widget(status, area);
 }
 
-fn chart_data(area: Rect, data_points: &[DataPoint]) -> ChartData {
-    let x_label_counts = usize::from(area.width.saturating_sub(6) / 7);
-    let x_label_steps = (data_points.len() as f64 / x_label_counts.max(1) as f64)
-        .ceil()
-        .max(1.0) as usize;
-    let x_labels = data_points
+fn draw_info<B>(f: &mut Frame<'_, B>, area: Rect, history: &[Context], msg: &Messages)
+where
+    B: tui::backend::Backend,
+{
+    let lines = history
         .iter()
-        .step_by(x_label_steps)
-        .map(|d| Span::raw(d.dates.date.format("%d.%m").to_string()))
+        .map(|context| Spans::from(msg.context(context)))
         .collect::<Vec<_>>();
+    let info = Paragraph::new(lines).block(Block::default().borders(Borders::ALL));
+    f.render_widget(info, area);
+}
 
-    let x_axis = Axis::default()
-        .style(Style::default().fg(Color::Gray))
-        .bounds([0.0, data_points.len() as f64])
-        .labels(x_labels);
+/// The weekly average of the new cases, hospitalisations and deaths, where the
+/// latter two are shifted back by their best lag and scaled to the cases
+fn draw_lags<B>(
+    f: &mut Frame<'_, B>,
+    area: Rect,
+    data_points: &[DataPoint],
+    correlations: &[CrossCorrelation],
+    msg: &Messages,
+) where
+    B: tui::backend::Backend,
+{
+    let cases = weekly_mean(data_points, |d| f64::from(d.cases.reported));
+    let max_cases = cases.iter().copied().fold(0.0, f64::max);
+    let cases_name = msg.get(
+        MsgId::NewCases,
+        cases.last().copied().unwrap_or_default(),
+        None::<f64>,
+    );
+    let mut lines = vec![Line::new(
+        cases_name,
+        Color::Yellow,
+        cases
+            .into_iter()
+            .enumerate()
+            .map(|(x, y)| (x as f64, y))
+            .collect(),
+    )];
+
+    for correlation in correlations {
+        let values = weekly_mean(data_points, |d| correlation.series.value(d));
+        let max_value = values.iter().copied().fold(0.0, f64::max);
+        let scale = if max_value > 0.0 {
+            max_cases / max_value
+        } else {
+            1.0
+        };
+        let points = values
+            .iter()
+            .skip(correlation.best_lag)
+            .enumerate()
+            .map(|(x, y)| (x as f64, y * scale))
+            .collect();
+        let (id, color) = match correlation.series {
+            Series::Hospitalisations => (MsgId::LaggedHospitalisations, Color::Cyan),
+            Series::Deaths => (MsgId::LaggedDeaths, Color::Magenta),
+        };
+        let name = msg.get(
+            id,
+            correlation.best_lag as f64,
+            Some(correlation.best_coefficient),
+        );
+        lines.push(Line::new(name, color, points));
+    }
+
+    let x_axis = date_axis(
+        area,
+        data_points.iter().map(|d| d.dates.date),
+        data_points.len(),
+    );
+    draw_lines(f, area, lines, x_axis);
+}
+
+/// Trailing mean over the last 7 data points, or fewer at the start
+fn weekly_mean(data_points: &[DataPoint], value: impl Fn(&DataPoint) -> f64) -> Vec<f64> {
+    (0..data_points.len())
+        .map(|i| {
+            let days = &data_points[(i + 1).saturating_sub(7)..=i];
+            days.iter().map(&value).sum::<f64>() / days.len() as f64
+        })
+        .collect()
+}
+
+/// Every calendar year as its own line of the incidence over the day of the year
+fn draw_years<B>(f: &mut Frame<'_, B>, area: Rect, data_points: &[DataPoint], msg: &Messages)
+where
+    B: tui::backend::Backend,
+{
+    let today = data_points.last().map(|d| d.dates.date.ordinal());
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while start < data_points.len() {
+        let year = data_points[start].dates.date.year();
+        let len = data_points[start..]
+            .iter()
+            .take_while(|d| d.dates.date.year() == year)
+            .count();
+        let year_points = &data_points[start..start + len];
+        start += len;
+
+        let same_day = year_points
+            .iter()
+            .find(|d| Some(d.dates.date.ordinal()) == today)
+            .map(|d| d.incidence_calculated);
+        let points = year_points
+            .iter()
+            .map(|d| (f64::from(d.dates.date.ordinal()), d.incidence_calculated))
+            .collect();
+        let name = msg.get(MsgId::Year, year, same_day);
+        let color = if start == data_points.len() {
+            Color::Red
+        } else {
+            PALETTE[lines.len() % PALETTE.len()]
+        };
+        lines.push(Line::new(name, color, points));
+    }
+
+    let x_axis = day_of_year_axis(area);
+    draw_lines(f, area, lines, x_axis);
+}
+
+/// Every wave as its own line of the incidence, re-based to the same day
+fn draw_waves<B>(
+    f: &mut Frame<'_, B>,
+    area: Rect,
+    data_points: &[DataPoint],
+    waves: &[Wave],
+    alignment: Alignment,
+    msg: &Messages,
+) where
+    B: tui::backend::Backend,
+{
+    let current = waves.len().saturating_sub(1);
+    let lines = waves
+        .iter()
+        .enumerate()
+        .map(|(number, wave)| {
+            let origin = match alignment {
+                Alignment::Start => wave.start_date(data_points),
+                Alignment::Peak => wave.peak_date(data_points),
+            };
+            let points = data_points[wave.start..wave.end]
+                .iter()
+                .map(|d| {
+                    let x = (d.dates.date - origin).num_days() as f64;
+                    (x, d.incidence_calculated)
+                })
+                .collect();
+            let name = msg.get(
+                MsgId::Wave,
+                number as f64 + 1.0,
+                Some(wave.peak_incidence(data_points)),
+            );
+            let color = if number == current {
+                Color::Red
+            } else {
+                PALETTE[number % PALETTE.len()]
+            };
+            Line::new(name, color, points)
+        })
+        .collect::<Vec<_>>();
+
+    let (min_day, max_day) = lines
+        .iter()
+        .flat_map(|l| l.points.iter().map(|(x, _)| *x))
+        .fold((0.0, 0.0), |(min, max), x| {
+            (f64::min(min, x), f64::max(max, x))
+        });
+    let x_axis = day_axis(area, min_day, max_day);
+    draw_lines(f, area, lines, x_axis);
+}
+
+const PALETTE: [Color; 6] = [
+    Color::Blue,
+    Color::Green,
+    Color::Cyan,
+    Color::Magenta,
+    Color::Yellow,
+    Color::LightBlue,
+];
+
+fn draw_trend<B>(
+    f: &mut Frame<'_, B>,
+    area: Rect,
+    data_points: &[DataPoint],
+    trend: &[Option<f64>],
+    msg: &Messages,
+) where
+    B: tui::backend::Backend,
+{
+    let trend = indexed_points(trend);
+    let name = msg.get(
+        MsgId::Trend,
+        trend.last().copied().unwrap_or_default().1,
+        None::<f64>,
+    );
+    let x_axis = date_axis(
+        area,
+        data_points.iter().map(|d| d.dates.date),
+        data_points.len(),
+    );
+    draw_lines(f, area, vec![Line::new(name, Color::Yellow, trend)], x_axis);
+}
+
+fn draw_rates<B>(
+    f: &mut Frame<'_, B>,
+    area: Rect,
+    data_points: &[DataPoint],
+    rates: &Rates,
+    index: usize,
+    msg: &Messages,
+) where
+    B: tui::backend::Backend,
+{
+    let lines = [
+        (
+            MsgId::CaseFatalityRate,
+            Color::Magenta,
+            &rates.case_fatality,
+        ),
+        (
+            MsgId::HospitalisationRate,
+            Color::Cyan,
+            &rates.hospitalisation,
+        ),
+        (
+            MsgId::LaggedCaseFatalityRate,
+            Color::Red,
+            &rates.lagged_case_fatality,
+        ),
+    ]
+    .iter()
+    .map(|(id, color, rate)| {
+        let points = indexed_points(rate.get(index..).unwrap_or_default());
+        let name = msg.get(
+            *id,
+            points.last().copied().unwrap_or_default().1,
+            None::<f64>,
+        );
+        Line::new(name, *color, points)
+    })
+    .collect();
+    let x_axis = date_axis(
+        area,
+        data_points.iter().map(|d| d.dates.date),
+        data_points.len(),
+    );
+    draw_lines(f, area, lines, x_axis);
+}
+
+fn indexed_points(values: &[Option<f64>]) -> Vec<(f64, f64)> {
+    values
+        .iter()
+        .enumerate()
+        .filter_map(|(x, y)| y.map(|y| (x as f64, y)))
+        .collect()
+}
+
+#[derive(Debug)]
+struct Line {
+    name: String,
+    color: Color,
+    points: Vec<(f64, f64)>,
+}
+
+impl Line {
+    fn new(name: String, color: Color, points: Vec<(f64, f64)>) -> Self {
+        Self {
+            name,
+            color,
+            points,
+        }
+    }
+}
+
+fn draw_lines<B>(f: &mut Frame<'_, B>, area: Rect, lines: Vec<Line>, x_axis: Axis<'static>)
+where
+    B: tui::backend::Backend,
+{
+    let (min_bound, max_bound) = lines
+        .iter()
+        .flat_map(|l| l.points.iter().map(|(_, y)| *y))
+        .fold((0.0, 0.0), |(min, max), y| {
+            (f64::min(min, y), f64::max(max, y))
+        });
+    let steps = lines
+        .iter()
+        .map(|l| l.points.len())
+        .max()
+        .unwrap_or_default();
+    let y_axis = value_axis(min_bound, max_bound, steps);
+
+    let datasets = lines
+        .iter()
+        .map(|line| {
+            Dataset::default()
+                .name(line.name.as_str())
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(line.color))
+                .graph_type(GraphType::Line)
+                .data(&line.points)
+        })
+        .collect();
+
+    let chart = Chart::new(datasets)
+        .block(Block::default().borders(Borders::ALL))
+        .hidden_legend_constraints((Constraint::Percentage(100), Constraint::Percentage(100)))
+        .x_axis(x_axis)
+        .y_axis(y_axis);
+
+    f.render_widget(chart, area);
+}
+
+fn chart_data(
+    area: Rect,
+    data_points: &[DataPoint],
+    projection: Option<&Projection>,
+    nowcast: &[NowcastPoint],
+    wave_peaks: &[usize],
+) -> ChartData {
+    let projected_points = projection.map_or(&[][..], |p| &p.points[..]);
+    let x_len = data_points.len() + projected_points.len();
+
+    let x_axis = date_axis(
+        area,
+        data_points
+            .iter()
+            .map(|d| d.dates.date)
+            .chain(projected_points.iter().map(|p| p.date)),
+        x_len,
+    );
 
     let min_bound = data_points
         .iter()
@@ -146,40 +691,22 @@ fn chart_data(area: Rect, data_points: &[DataPoint]) -> ChartData {
         })
         .max()
         .unwrap_or_default() as f64;
+    let max_bound = projected_points
+        .iter()
+        .map(|p| p.total_cases.value)
+        .fold(max_bound, f64::max);
 
     let max_incidence = data_points
         .iter()
         .map(|d| d.incidence_calculated)
+        .chain(projected_points.iter().map(|p| p.incidence.value))
+        .chain(nowcast.iter().map(|p| p.incidence.upper))
         .max_by(|a, b| a.partial_cmp(b).unwrap())
         .unwrap_or_default();
 
     let incidence_scale = max_bound / max_incidence;
 
-    let bound_step = (max_bound - min_bound) / data_points.len() as f64;
-    let y_bounds = std::iter::successors(Some(min_bound), |y| {
-        Some(*y + bound_step).filter(|y| *y <= max_bound)
-    })
-    .map(|y| y.round() as u32)
-    .scan(None::<u32>, |seen, y| {
-        Some(match seen {
-            None => {
-                *seen = Some(y);
-                (y, true)
-            }
-            Some(prev) => {
-                let prev = std::mem::replace(prev, y);
-                (y, y > prev)
-            }
-        })
-    })
-    .filter_map(|(y, distinct)| if distinct { Some(y) } else { None })
-    .map(|y| Span::raw(format!("{:.1}", y)))
-    .collect::<Vec<_>>();
-
-    let y_axis = Axis::default()
-        .style(Style::default().fg(Color::Gray))
-        .bounds([min_bound, max_bound])
-        .labels(y_bounds);
+    let y_axis = value_axis(min_bound, max_bound, data_points.len());
 
     let recoveries = data_points
         .iter()
@@ -218,6 +745,99 @@ fn chart_data(area: Rect, data_points: &[DataPoint]) -> ChartData {
         .map(|(x, y)| (x as f64, y.incidence_calculated * incidence_scale))
         .collect::<Vec<_>>();
 
+    let wave_peak_count = wave_peaks.len();
+    let marker_points = usize::from(area.height) * 4;
+    let marker_step = (max_bound - min_bound) / marker_points.max(1) as f64;
+    let wave_peaks = wave_peaks
+        .iter()
+        .flat_map(|&x| {
+            (0..=marker_points).map(move |y| (x as f64, min_bound + y as f64 * marker_step))
+        })
+        .collect::<Vec<_>>();
+
+    let last_x = data_points.len().saturating_sub(1);
+    // the projection continues from the last data point, sampled once per braille dot
+    let dot_width = x_len as f64 / (f64::from(area.width) * 2.0).max(1.0);
+    let (last_incidence, last_cases) = data_points
+        .last()
+        .map(|d| {
+            (
+                d.incidence_calculated * incidence_scale,
+                f64::from(d.cases.total),
+            )
+        })
+        .unwrap_or_default();
+    let projected_line = |last: f64, value: fn(&ProjectedPoint) -> f64, scale: f64| {
+        data_points
+            .last()
+            .map(|_| (last_x as f64, last))
+            .into_iter()
+            .chain(
+                projected_points
+                    .iter()
+                    .enumerate()
+                    .map(|(x, p)| ((last_x + x + 1) as f64, value(p) * scale)),
+            )
+            .collect::<Vec<_>>()
+    };
+
+    let projected_incidences = sampled_line(
+        &projected_line(last_incidence, |p| p.incidence.value, incidence_scale),
+        dot_width,
+        dashed,
+    );
+    let projected_incidence_band = [
+        projected_line(last_incidence, |p| p.incidence.lower, incidence_scale),
+        projected_line(last_incidence, |p| p.incidence.upper, incidence_scale),
+    ]
+    .iter()
+    .flat_map(|line| sampled_line(line, dot_width, dotted))
+    .collect::<Vec<_>>();
+
+    let projected_cases = sampled_line(
+        &projected_line(last_cases, |p| p.total_cases.value, 1.0),
+        dot_width,
+        dashed,
+    );
+    let projected_cases_band = [
+        projected_line(last_cases, |p| p.total_cases.lower, 1.0),
+        projected_line(last_cases, |p| p.total_cases.upper, 1.0),
+    ]
+    .iter()
+    .flat_map(|line| sampled_line(line, dot_width, dotted))
+    .collect::<Vec<_>>();
+
+    // the nowcast always covers the latest data points, even when zoomed in
+    let nowcast = &nowcast[nowcast.len().saturating_sub(data_points.len())..];
+    let first_nowcast_x = (last_x + 1).saturating_sub(nowcast.len());
+    let nowcast_incidences = nowcast
+        .iter()
+        .enumerate()
+        .map(|(x, p)| {
+            (
+                (first_nowcast_x + x) as f64,
+                p.incidence.value * incidence_scale,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // shade the uncertainty of the nowcast by filling it with points
+    let nowcast_band = nowcast
+        .iter()
+        .enumerate()
+        .flat_map(|(x, p)| {
+            let lower = p.incidence.lower * incidence_scale;
+            let upper = p.incidence.upper * incidence_scale;
+            let steps = ((upper - lower) / marker_step.max(f64::EPSILON)).ceil() as usize;
+            (0..=steps.min(marker_points)).map(move |y| {
+                (
+                    (first_nowcast_x + x) as f64,
+                    (lower + y as f64 * marker_step).min(upper),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+
     let (
         cases_increase,
         deaths_increase,
@@ -261,6 +881,18 @@ fn chart_data(area: Rect, data_points: &[DataPoint]) -> ChartData {
         actives,
         cases,
         incidences,
+        projected: projected_points.last().copied(),
+        projected_incidences,
+        projected_incidence_band,
+        projected_cases,
+        projected_cases_band,
+        nowcast_incidences,
+        nowcast_band,
+        nowcast_range: nowcast
+            .last()
+            .map(|p| (p.incidence.lower, p.incidence.upper)),
+        wave_peaks,
+        wave_peak_count,
         current_incidence,
         cases_increase,
         actives_increase,
@@ -268,13 +900,120 @@ fn chart_data(area: Rect, data_points: &[DataPoint]) -> ChartData {
         hospitalizations_increase,
         recoveries_increase,
         incidence_increase,
+        incidence_scale,
         x_axis,
         y_axis,
     }
 }
 
+/// Points along the straight segments between the given points, spaced `step` apart
+/// on the x axis, that are kept where `visible` is true for their running index
+fn sampled_line(points: &[(f64, f64)], step: f64, visible: fn(usize) -> bool) -> Vec<(f64, f64)> {
+    let mut samples = Vec::new();
+    let mut index = 0;
+    for segment in points.windows(2) {
+        let ((x0, y0), (x1, y1)) = (segment[0], segment[1]);
+        let mut x = x0;
+        while x < x1 {
+            if visible(index) {
+                samples.push((x, y0 + (y1 - y0) * (x - x0) / (x1 - x0)));
+            }
+            index += 1;
+            x += step;
+        }
+    }
+    if let Some(&last) = points.last() {
+        if visible(index) {
+            samples.push(last);
+        }
+    }
+    samples
+}
+
+fn dashed(index: usize) -> bool {
+    index % 6 < 4
+}
+
+fn dotted(index: usize) -> bool {
+    index % 3 == 0
+}
+
+fn date_axis(area: Rect, dates: impl Iterator<Item = Date<Utc>>, len: usize) -> Axis<'static> {
+    let x_label_counts = usize::from(area.width.saturating_sub(6) / 7);
+    let x_label_steps = (len as f64 / x_label_counts.max(1) as f64).ceil().max(1.0) as usize;
+    let x_labels = dates
+        .step_by(x_label_steps)
+        .map(|d| Span::raw(d.format("%d.%m").to_string()))
+        .collect::<Vec<_>>();
+
+    Axis::default()
+        .style(Style::default().fg(Color::Gray))
+        .bounds([0.0, len as f64])
+        .labels(x_labels)
+}
+
+fn day_axis(area: Rect, min_day: f64, max_day: f64) -> Axis<'static> {
+    let x_label_counts = usize::from(area.width.saturating_sub(6) / 7).max(2);
+    let x_label_step = (max_day - min_day) / (x_label_counts - 1) as f64;
+    let x_labels = (0..x_label_counts)
+        .map(|i| Span::raw(format!("{:+.0}", min_day + i as f64 * x_label_step)))
+        .collect::<Vec<_>>();
+
+    Axis::default()
+        .style(Style::default().fg(Color::Gray))
+        .bounds([min_day, max_day])
+        .labels(x_labels)
+}
+
+fn day_of_year_axis(area: Rect) -> Axis<'static> {
+    let x_label_counts = usize::from(area.width.saturating_sub(6) / 7).clamp(2, 13);
+    let x_label_step = 365.0 / (x_label_counts - 1) as f64;
+    let first_day = Utc.ymd(2021, 1, 1);
+    let x_labels = (0..x_label_counts)
+        .map(|i| {
+            let day = first_day + chrono::Duration::days((i as f64 * x_label_step) as i64);
+            Span::raw(day.format("%d.%m").to_string())
+        })
+        .collect::<Vec<_>>();
+
+    Axis::default()
+        .style(Style::default().fg(Color::Gray))
+        .bounds([1.0, 366.0])
+        .labels(x_labels)
+}
+
+fn value_axis(min_bound: f64, max_bound: f64, steps: usize) -> Axis<'static> {
+    // at least 1 to guarantee progress, the labels are rounded to integers anyway
+    let bound_step = ((max_bound - min_bound) / steps as f64).max(1.0);
+    let y_bounds = std::iter::successors(Some(min_bound), |y| {
+        Some(*y + bound_step).filter(|y| *y <= max_bound)
+    })
+    .map(|y| y.round() as u32)
+    .scan(None::<u32>, |seen, y| {
+        Some(match seen {
+            None => {
+                *seen = Some(y);
+                (y, true)
+            }
+            Some(prev) => {
+                let prev = std::mem::replace(prev, y);
+                (y, y > prev)
+            }
+        })
+    })
+    .filter_map(|(y, distinct)| if distinct { Some(y) } else { None })
+    .map(|y| Span::raw(format!("{:.1}", y)))
+    .collect::<Vec<_>>();
+
+    Axis::default()
+        .style(Style::default().fg(Color::Gray))
+        .bounds([min_bound, max_bound])
+        .labels(y_bounds)
+}
+
 fn draw_chart_data<B: tui::backend::Backend>(
     f: &mut Frame<'_, B>,
+    area: Rect,
     data: ChartData,
     msg: &Messages,
 ) {
@@ -309,7 +1048,7 @@ fn draw_chart_data<B: tui::backend::Backend>(
         Some(data.incidence_increase),
     );
 
-    let datasets = vec![
+    let mut datasets = vec![
         Dataset::default()
             .name(recovered)
             .marker(symbols::Marker::Braille)
@@ -352,13 +1091,105 @@ fn draw_chart_data<B: tui::backend::Backend>(
             .data(&data.cases),
     ];
 
+    if data.wave_peak_count > 0 {
+        let wave_peaks = msg.get(MsgId::WavePeaks, data.wave_peak_count as f64, None::<f64>);
+        datasets.insert(
+            0,
+            Dataset::default()
+                .name(wave_peaks)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::DarkGray))
+                .graph_type(GraphType::Scatter)
+                .data(&data.wave_peaks),
+        );
+    }
+    if let Some((lower, upper)) = data.nowcast_range {
+        let nowcast_range = msg.get(MsgId::NowcastRange, lower, Some(upper));
+        datasets.insert(
+            0,
+            Dataset::default()
+                .name(nowcast_range)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Red).add_modifier(Modifier::DIM))
+                .graph_type(GraphType::Scatter)
+                .data(&data.nowcast_band),
+        );
+    }
+    if let Some(&(_, nowcast_incidence)) = data.nowcast_incidences.last() {
+        let nowcast_incidence = nowcast_incidence / data.incidence_scale;
+        let nowcast_incidence = msg.get(
+            MsgId::NowcastIncidence,
+            nowcast_incidence,
+            Some(nowcast_incidence - data.current_incidence),
+        );
+        datasets.push(
+            Dataset::default()
+                .name(nowcast_incidence)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::LightRed))
+                .graph_type(GraphType::Line)
+                .data(&data.nowcast_incidences),
+        );
+    }
+    if let Some(projected) = data.projected {
+        let incidence = projected.incidence;
+        let incidence_range = msg.get(
+            MsgId::ProjectedIncidenceRange,
+            incidence.lower,
+            Some(incidence.upper),
+        );
+        let cases = projected.total_cases;
+        let cases_range = msg.get(MsgId::ProjectedCasesRange, cases.lower, Some(cases.upper));
+        datasets.insert(
+            0,
+            Dataset::default()
+                .name(incidence_range)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Red).add_modifier(Modifier::DIM))
+                .graph_type(GraphType::Scatter)
+                .data(&data.projected_incidence_band),
+        );
+        datasets.insert(
+            0,
+            Dataset::default()
+                .name(cases_range)
+                .marker(symbols::Marker::Braille)
+                .style(
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::DIM),
+                )
+                .graph_type(GraphType::Scatter)
+                .data(&data.projected_cases_band),
+        );
+
+        let projected_incidence = msg.get(MsgId::ProjectedIncidence, incidence.value, None::<f64>);
+        datasets.push(
+            Dataset::default()
+                .name(projected_incidence)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Red))
+                .graph_type(GraphType::Scatter)
+                .data(&data.projected_incidences),
+        );
+        let projected_cases = msg.get(MsgId::ProjectedCases, cases.value, None::<f64>);
+        datasets.push(
+            Dataset::default()
+                .name(projected_cases)
+                .marker(symbols::Marker::Braille)
+                .style(Style::default().fg(Color::Yellow))
+                .graph_type(GraphType::Scatter)
+                .data(&data.projected_cases),
+        );
+    }
+
     let chart = Chart::new(datasets)
         .block(Block::default().borders(Borders::ALL))
         .hidden_legend_constraints((Constraint::Percentage(100), Constraint::Percentage(100)))
         .x_axis(data.x_axis)
         .y_axis(data.y_axis);
 
-    f.render_widget(chart, f.size());
+    f.render_widget(chart, area);
 }
 
 #[derive(Debug)]
@@ -369,6 +1200,16 @@ struct ChartData {
     actives: Vec<(f64, f64)>,
     cases: Vec<(f64, f64)>,
     incidences: Vec<(f64, f64)>,
+    projected: Option<ProjectedPoint>,
+    projected_incidences: Vec<(f64, f64)>,
+    projected_incidence_band: Vec<(f64, f64)>,
+    projected_cases: Vec<(f64, f64)>,
+    projected_cases_band: Vec<(f64, f64)>,
+    nowcast_incidences: Vec<(f64, f64)>,
+    nowcast_band: Vec<(f64, f64)>,
+    nowcast_range: Option<(f64, f64)>,
+    wave_peaks: Vec<(f64, f64)>,
+    wave_peak_count: usize,
     current_incidence: f64,
     cases_increase: i32,
     deaths_increase: i32,
@@ -376,6 +1217,7 @@ struct ChartData {
     hospitalizations_increase: i32,
     recoveries_increase: i32,
     incidence_increase: f64,
+    incidence_scale: f64,
     x_axis: Axis<'static>,
     y_axis: Axis<'static>,
 }
@@ -387,4 +1229,31 @@ enum Event {
     ZoomOut(usize),
     LastWeek(usize),
     AllData,
+    Toggle(View),
+    ToggleInfo,
+    /// The data was refreshed or the refresh failed, only the charts need to be drawn again
+    Refreshed,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct State {
+    index: usize,
+    view: View,
+    info: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum View {
+    Totals,
+    Trend,
+    Rates,
+    Waves(Alignment),
+    Years,
+    Lags,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Alignment {
+    Start,
+    Peak,
 }
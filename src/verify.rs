@@ -0,0 +1,181 @@
+//! Checks that the cache can be trusted, without downloading anything.
+
+use crate::{api, cache, data::DataPoint};
+use std::{fmt::Display, path::Path};
+use tracing::instrument;
+
+/// Differences below this are rounding noise of the calculated incidence
+const TOLERANCE: f64 = 1e-6;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    /// The check could not be run, this is not a problem of the cache
+    Skipped(String),
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Passed => f.write_str("ok"),
+            Outcome::Failed(reason) => write!(f, "failed, {}", reason),
+            Outcome::Skipped(reason) => write!(f, "skipped, {}", reason),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Check {
+    pub name: &'static str,
+    pub outcome: Outcome,
+}
+
+/// Runs all checks, the later ones are skipped if the cache cannot be read
+#[instrument]
+pub fn verify(file: &Path) -> Vec<Check> {
+    let check = |name, outcome| Check { name, outcome };
+
+    let data = match cache::read_cache_file(file) {
+        Ok(data) => data,
+        Err(e) => {
+            let skipped = || Outcome::Skipped(String::from("the cache could not be parsed"));
+            return vec![
+                check("parse", Outcome::Failed(format!("{}", e))),
+                check("checksum", skipped()),
+                check("dates", skipped()),
+                check("corrections", skipped()),
+            ];
+        }
+    };
+
+    vec![
+        check("parse", Outcome::Passed),
+        check("checksum", check_checksum(file)),
+        check("dates", check_dates(&data.attributes)),
+        check(
+            "corrections",
+            check_corrections(&data.attributes, data.population),
+        ),
+    ]
+}
+
+/// The checksum was stored when the cache was written
+fn check_checksum(file: &Path) -> Outcome {
+    match cache::checksums(file) {
+        Ok(Some(checksums)) if checksums.stored == checksums.computed => Outcome::Passed,
+        Ok(Some(checksums)) => Outcome::Failed(format!(
+            "the data has {}, but the cache was written with {}",
+            checksums.computed, checksums.stored
+        )),
+        Ok(None) => Outcome::Skipped(String::from(
+            "the cache has no checksum, it was written by an older covidd",
+        )),
+        Err(e) => Outcome::Failed(format!("{}", e)),
+    }
+}
+
+fn check_dates(data_points: &[DataPoint]) -> Outcome {
+    if data_points.is_empty() {
+        return Outcome::Failed(String::from("there are no data points"));
+    }
+    match data_points
+        .windows(2)
+        .find(|w| w[1].dates.date <= w[0].dates.date)
+    {
+        Some(w) => Outcome::Failed(format!(
+            "{} follows {}",
+            w[1].dates.date.naive_utc(),
+            w[0].dates.date.naive_utc()
+        )),
+        None => Outcome::Passed,
+    }
+}
+
+/// Correcting the data again must not change it
+fn check_corrections(data_points: &[DataPoint], population: Option<u32>) -> Outcome {
+    let population = match population {
+        Some(population) => f64::from(population),
+        None => {
+            return Outcome::Skipped(String::from(
+                "the population is not in the cache, it was written by an older covidd",
+            ))
+        }
+    };
+
+    let corrected = api::correct_data(vec![data_points.to_vec()], population);
+    let changed = data_points
+        .iter()
+        .zip(&corrected)
+        .filter(|(d, c)| {
+            (d.incidence_calculated - c.incidence_calculated).abs() > TOLERANCE
+                || d.cases.increase != c.cases.increase
+                || d.deaths.increase != c.deaths.increase
+                || d.recoveries.increase != c.recoveries.increase
+                || d.hospitalisations.increase != c.hospitalisations.increase
+        })
+        .map(|(d, _)| d.dates.date.naive_utc())
+        .collect::<Vec<_>>();
+
+    match changed.first() {
+        Some(first) => Outcome::Failed(format!(
+            "{} of {} days differ from their recalculation, the first on {}",
+            changed.len(),
+            data_points.len(),
+            first
+        )),
+        None => Outcome::Passed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    fn data_point(day: u32, cases: u32) -> DataPoint {
+        let mut data_point = fixtures::day(day);
+        data_point.cases.total = cases;
+        data_point.cases.reported = cases / 10;
+        data_point
+    }
+
+    fn corrected() -> Vec<DataPoint> {
+        let data = (1..=20)
+            .map(|day| data_point(day, day * 100))
+            .collect::<Vec<_>>();
+        api::correct_data(vec![data], 500_000.0)
+    }
+
+    #[test]
+    fn corrected_data_passes() {
+        let data = corrected();
+
+        assert_eq!(check_dates(&data), Outcome::Passed);
+        assert_eq!(check_corrections(&data, Some(500_000)), Outcome::Passed);
+    }
+
+    #[test]
+    fn dates_out_of_order_fail() {
+        let mut data = corrected();
+        data.swap(3, 4);
+
+        assert_eq!(
+            check_dates(&data),
+            Outcome::Failed(String::from("2021-11-04 follows 2021-11-05"))
+        );
+    }
+
+    #[test]
+    fn changed_incidence_fails() {
+        let mut data = corrected();
+        data[10].incidence_calculated += 1.0;
+
+        assert_eq!(
+            check_corrections(&data, Some(500_000)),
+            Outcome::Failed(String::from(
+                "1 of 20 days differ from their recalculation, the first on 2021-11-11"
+            ))
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use crate::data::DataPoint;
+use chrono::{Date, Utc};
+use std::cmp::Ordering;
+use tracing::{debug, instrument};
+
+pub const DEFAULT_PROMINENCE: f64 = 50.0;
+
+/// A wave in the incidence, as indices into the data points
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Wave {
+    /// The lowest point before the peak, inclusive
+    pub start: usize,
+    /// The highest point of the wave
+    pub peak: usize,
+    /// The lowest point after the peak, exclusive
+    pub end: usize,
+    /// How far the peak stands out from the surrounding lows
+    pub prominence: f64,
+}
+
+impl Wave {
+    pub fn start_date(&self, data_points: &[DataPoint]) -> Date<Utc> {
+        data_points[self.start].dates.date
+    }
+
+    pub fn peak_date(&self, data_points: &[DataPoint]) -> Date<Utc> {
+        data_points[self.peak].dates.date
+    }
+
+    pub fn end_date(&self, data_points: &[DataPoint]) -> Date<Utc> {
+        data_points[self.end - 1].dates.date
+    }
+
+    pub fn peak_incidence(&self, data_points: &[DataPoint]) -> f64 {
+        data_points[self.peak].incidence_calculated
+    }
+
+    pub fn days(&self, data_points: &[DataPoint]) -> i64 {
+        (self.end_date(data_points) - self.start_date(data_points)).num_days() + 1
+    }
+
+    pub fn cases(&self, data_points: &[DataPoint]) -> u64 {
+        data_points[self.start..self.end]
+            .iter()
+            .map(|d| u64::from(d.cases.reported))
+            .sum()
+    }
+}
+
+/// Finds all peaks in the calculated incidence that stand out by at least
+/// `min_prominence` and splits the data at the lowest points between them.
+#[instrument(skip(data_points))]
+pub fn detect(data_points: &[DataPoint], min_prominence: f64) -> Vec<Wave> {
+    let incidences = data_points
+        .iter()
+        .map(|d| d.incidence_calculated)
+        .collect::<Vec<_>>();
+
+    let peaks = local_maxima(&incidences)
+        .filter_map(|peak| {
+            let prominence = prominence(&incidences, peak);
+            if prominence >= min_prominence {
+                Some((peak, prominence))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    debug!("Found {} peaks", peaks.len());
+
+    let mut waves = Vec::with_capacity(peaks.len());
+    let mut start = lowest(&incidences, 0, peaks.first().map_or(0, |(p, _)| *p));
+    for (i, &(peak, prominence)) in peaks.iter().enumerate() {
+        let next_peak = peaks.get(i + 1).map_or(incidences.len(), |(p, _)| *p);
+        let low = lowest(&incidences, peak, next_peak);
+        let end = if next_peak == incidences.len() {
+            low + 1
+        } else {
+            low
+        };
+        waves.push(Wave {
+            start,
+            peak,
+            end,
+            prominence,
+        });
+        start = low;
+    }
+    waves
+}
+
+/// Indices that are higher than their predecessor and not lower than their successor
+fn local_maxima(values: &[f64]) -> impl Iterator<Item = usize> + '_ {
+    (1..values.len()).filter(move |&i| {
+        values[i] > values[i - 1] && values.get(i + 1).map_or(true, |next| values[i] >= *next)
+    })
+}
+
+/// The height of a peak above the higher of the two lowest points that
+/// separate it from a higher peak on either side.
+///
+/// A peak at the end of the data is only compared to the side with data,
+/// so that an ongoing wave can already be detected.
+fn prominence(values: &[f64], peak: usize) -> f64 {
+    let height = values[peak];
+    let left_base = base(values[..peak].iter().rev(), height);
+    let right_base = base(values[peak + 1..].iter(), height);
+    let base = match (left_base, right_base) {
+        (Some(left), Some(right)) => left.max(right),
+        (Some(base), None) | (None, Some(base)) => base,
+        (None, None) => height,
+    };
+    height - base
+}
+
+/// The lowest value on one side of a peak until a higher value is reached
+fn base<'a>(side: impl Iterator<Item = &'a f64>, height: f64) -> Option<f64> {
+    side.take_while(|v| **v <= height).copied().reduce(f64::min)
+}
+
+/// Index of the lowest value in `from..to`, the last one if there are multiple.
+/// Missing values are skipped.
+fn lowest(values: &[f64], from: usize, to: usize) -> usize {
+    (from..to.max(from + 1).min(values.len()))
+        .rev()
+        .filter(|i| !values[*i].is_nan())
+        .min_by(|a, b| {
+            values[*a]
+                .partial_cmp(&values[*b])
+                .unwrap_or(Ordering::Equal)
+        })
+        .unwrap_or(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::fixtures;
+
+    fn data(incidences: &[f64]) -> Vec<DataPoint> {
+        incidences
+            .iter()
+            .enumerate()
+            .map(|(i, &incidence)| {
+                let mut data_point = fixtures::day(i as u32 + 1);
+                data_point.incidence_calculated = incidence;
+                data_point
+            })
+            .collect()
+    }
+
+    const INCIDENCES: [f64; 8] = [0.0, 10.0, 100.0, 20.0, 30.0, 200.0, 50.0, 0.0];
+
+    #[test]
+    fn waves_are_split_at_the_lows_between_peaks() {
+        let waves = detect(&data(&INCIDENCES), 50.0);
+
+        assert_eq!(
+            waves,
+            vec![
+                Wave {
+                    start: 0,
+                    peak: 2,
+                    end: 3,
+                    prominence: 80.0,
+                },
+                Wave {
+                    start: 3,
+                    peak: 5,
+                    end: 8,
+                    prominence: 200.0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn peaks_below_the_prominence_are_part_of_a_wave() {
+        let waves = detect(&data(&INCIDENCES), 100.0);
+
+        assert_eq!(
+            waves,
+            vec![Wave {
+                start: 0,
+                peak: 5,
+                end: 8,
+                prominence: 200.0,
+            }]
+        );
+    }
+
+    #[test]
+    fn missing_values_are_not_the_lowest() {
+        let values = [5.0, f64::NAN, 1.0, f64::NAN];
+
+        assert_eq!(lowest(&values, 0, 4), 2);
+        assert_eq!(lowest(&[f64::NAN], 0, 1), 0);
+    }
+}